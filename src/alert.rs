@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinHandle;
+
+use crate::config::{Config, Notifier, SmtpSecurity, expand_env};
+use crate::model::{
+    CheckHistory, CheckResult, CheckStatus, LatencyPhase, RetryPolicy, StateChange,
+};
+
+// 通知1件あたりの送信タイムアウト
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 送信に失敗したときの再試行（1秒から倍々）
+const SEND_RETRY: RetryPolicy = RetryPolicy {
+    retries: 2,
+    backoff_ms: 1000,
+    max_backoff_ms: 5000,
+};
+
+fn label(change: StateChange) -> &'static str {
+    match change {
+        StateChange::Down => "DOWN",
+        StateChange::Recovered => "RECOVERED",
+        StateChange::SlaBreach => "SLA BREACH",
+        StateChange::SlaRecovered => "SLA RECOVERED",
+        StateChange::Flapping => "FLAPPING",
+        StateChange::FlappingStopped => "FLAPPING STOPPED",
+    }
+}
+
+// 通知先に送る内容（webhook / command にはこの JSON をそのまま渡す）
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub event: StateChange,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub timestamp: DateTime<Utc>,
+    pub status: CheckStatus,
+    pub reason: Option<String>,
+    pub status_code: Option<u16>,
+    pub latency_ms: Option<u64>, // latency_sla があれば窓の百分位
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percentile: Option<f64>,
+    pub acceptable_latency_ms: u64,
+    pub latency_phase: LatencyPhase,
+    pub flips: usize, // flap 判定の窓内での OK⇔NG の入れ替わり回数
+}
+
+impl Alert {
+    fn new(event: StateChange, history: &CheckHistory, result: &CheckResult) -> Self {
+        Self {
+            event,
+            url: history.url.clone(),
+            name: history.name.clone(),
+            group: history.group.clone(),
+            tags: history.tags.clone(),
+            timestamp: result.timestamp,
+            status: result.status,
+            reason: result.reason(),
+            status_code: result.status_code,
+            latency_ms: match history.latency_sla {
+                Some(_) => result
+                    .diff_from_acceptable
+                    .map(|d| (history.acceptable_latency_ms as i128 + d).max(0) as u64),
+                None => result
+                    .latency(history.latency_phase)
+                    .map(|d| d.as_millis() as u64),
+            },
+            percentile: history.latency_sla.map(|sla| sla.percentile),
+            acceptable_latency_ms: history.acceptable_latency_ms,
+            latency_phase: history.latency_phase,
+            flips: history.state.flips.len(),
+        }
+    }
+
+    // チャット・メール用の1行の要約
+    pub fn summary(&self) -> String {
+        let detail = match self.event {
+            StateChange::SlaBreach | StateChange::SlaRecovered => format!(
+                "{}{} ms (limit {} ms, {})",
+                self.percentile
+                    .map_or(String::new(), |p| format!("p{} ", p)),
+                self.latency_ms.unwrap_or(0),
+                self.acceptable_latency_ms,
+                format!("{:?}", self.latency_phase).to_lowercase()
+            ),
+            StateChange::Down => self
+                .reason
+                .clone()
+                .unwrap_or_else(|| format!("{:?}", self.status)),
+            StateChange::Recovered | StateChange::FlappingStopped => format!("{:?}", self.status),
+            StateChange::Flapping => format!("{} state changes, notifications paused", self.flips),
+        };
+        match &self.name {
+            Some(name) => format!("[{}] {} ({}) {}", label(self.event), name, self.url, detail),
+            None => format!("[{}] {} {}", label(self.event), self.url, detail),
+        }
+    }
+}
+
+// 同じ種類の通知（Down と Recovered など）をまとめる区分
+fn category(change: StateChange) -> u8 {
+    match change {
+        StateChange::Down | StateChange::Recovered => 0,
+        StateChange::SlaBreach | StateChange::SlaRecovered => 1,
+        StateChange::Flapping | StateChange::FlappingStopped => 2,
+    }
+}
+
+// ターゲットごとの通知先を持ち、アラートを送る
+pub struct Alerter {
+    notifiers: HashMap<String, Arc<Notifier>>,
+    routes: HashMap<String, Vec<String>>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    // ターゲット・区分ごとに最後に送った変化。同じ変化は続けて送らない
+    sent: Mutex<HashMap<(String, u8), StateChange>>,
+    sending: Mutex<Vec<JoinHandle<()>>>, // 送信中の通知（終了時に待つ）
+}
+
+impl Alerter {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .context("failed to build the HTTP client for notifications")?;
+        let mut alerter = Self {
+            notifiers: HashMap::new(),
+            routes: HashMap::new(),
+            client,
+            retry: SEND_RETRY,
+            sent: Mutex::new(HashMap::new()),
+            sending: Mutex::new(Vec::new()),
+        };
+        alerter.configure(config);
+        Ok(alerter)
+    }
+
+    // 通知先とターゲットごとの送り先を設定し直す（送った記録は引き継ぐ）
+    pub fn configure(&mut self, config: &Config) {
+        self.notifiers = config
+            .notifiers
+            .iter()
+            .map(|(name, n)| (name.clone(), Arc::new(n.clone())))
+            .collect();
+        self.routes = config
+            .targets
+            .iter()
+            .filter(|t| !t.notify.is_empty())
+            .map(|t| {
+                // 同じ通知先を2回書いても1回だけ送る
+                let mut names = t.notify.clone();
+                let mut seen = std::collections::HashSet::new();
+                names.retain(|name| seen.insert(name.clone()));
+                (t.key().to_string(), names)
+            })
+            .collect();
+    }
+
+    // 前回送った同じ区分の変化と同じなら false（送らない）
+    fn first_time(&self, key: &str, change: StateChange) -> bool {
+        let Ok(mut sent) = self.sent.lock() else {
+            return true;
+        };
+        sent.insert((key.to_string(), category(change)), change) != Some(change)
+    }
+
+    // 状態の変化をそのターゲットの通知先へ送る（送信は裏で行う）
+    pub fn notify(&self, history: &CheckHistory, changes: &[StateChange]) {
+        let Some(names) = self.routes.get(history.key()) else {
+            return;
+        };
+        let Some(latest) = history.results.back() else {
+            return;
+        };
+
+        for alert in changes.iter().map(|&c| Alert::new(c, history, latest)) {
+            if !self.first_time(history.key(), alert.event) {
+                log::debug!("[ALERT] {} (already sent)", alert.summary());
+                continue;
+            }
+            println!("[ALERT] {}", alert.summary());
+            let alert = Arc::new(alert);
+
+            for name in names {
+                let Some(notifier) = self.notifiers.get(name).cloned() else {
+                    continue;
+                };
+                let client = self.client.clone();
+                let alert = alert.clone();
+                let name = name.clone();
+                let retry = self.retry;
+
+                let handle = tokio::spawn(async move {
+                    if let Err(e) = deliver(&client, &notifier, &alert, &name, retry).await {
+                        eprintln!("[ALERT] {} への通知に失敗しました: {:#}", name, e);
+                    }
+                });
+                if let Ok(mut sending) = self.sending.lock() {
+                    sending.retain(|h| !h.is_finished());
+                    sending.push(handle);
+                }
+            }
+        }
+    }
+
+    // 送信中の通知が終わるのを待つ
+    pub async fn flush(&self) {
+        let sending = match self.sending.lock() {
+            Ok(mut sending) => std::mem::take(&mut *sending),
+            Err(_) => return,
+        };
+        for handle in sending {
+            let _ = handle.await;
+        }
+    }
+}
+
+// 一時的な失敗なら retry に従って送り直す
+async fn deliver(
+    client: &reqwest::Client,
+    notifier: &Notifier,
+    alert: &Alert,
+    name: &str,
+    retry: RetryPolicy,
+) -> anyhow::Result<()> {
+    let mut attempt = 0;
+    loop {
+        match send(client, notifier, alert).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < retry.retries && retryable(notifier, &e) => {
+                attempt += 1;
+                let delay = retry.delay(attempt);
+                eprintln!(
+                    "[ALERT] {} への通知に失敗しました（{}ms 後に再送 {}/{}）: {:#}",
+                    name,
+                    delay.as_millis(),
+                    attempt,
+                    retry.retries,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// 送り直して意味のある失敗か（429 以外の 4xx や SMTP の恒久的なエラーは送り直さない。command は二重に実行しないよう送り直さない）
+fn retryable(notifier: &Notifier, err: &anyhow::Error) -> bool {
+    if matches!(notifier, Notifier::Command { .. }) {
+        return false;
+    }
+    if let Some(status) = err
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+    {
+        return status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+    }
+    if let Some(e) = err.downcast_ref::<lettre::transport::smtp::Error>() {
+        return !e.is_permanent();
+    }
+    true
+}
+
+async fn send(client: &reqwest::Client, notifier: &Notifier, alert: &Alert) -> anyhow::Result<()> {
+    match notifier {
+        Notifier::Webhook { url, headers } => {
+            let mut request = client.post(expand_env(url)).json(alert);
+            for (name, value) in headers {
+                request = request.header(name, expand_env(value));
+            }
+            request.send().await?.error_for_status()?;
+        }
+        Notifier::Slack { url } | Notifier::Teams { url } => {
+            let payload = serde_json::json!({ "text": alert.summary() });
+            client
+                .post(expand_env(url))
+                .json(&payload)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Notifier::Discord { url } => {
+            let payload = serde_json::json!({ "content": alert.summary() });
+            client
+                .post(expand_env(url))
+                .json(&payload)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Notifier::Email {
+            smtp_host,
+            smtp_port,
+            smtp_security,
+            username,
+            password,
+            from,
+            to,
+        } => {
+            let message = email_message(alert, from, to)?;
+
+            let host = expand_env(smtp_host);
+            let mut transport = match smtp_security {
+                SmtpSecurity::Starttls => {
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?
+                }
+                SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+                SmtpSecurity::None => {
+                    AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)
+                }
+            }
+            .port(*smtp_port)
+            .timeout(Some(SEND_TIMEOUT));
+            if let (Some(username), Some(password)) = (username, password) {
+                transport = transport
+                    .credentials(Credentials::new(expand_env(username), expand_env(password)));
+            }
+            transport.build().send(message).await?;
+        }
+        Notifier::Command { command } => {
+            let (program, args) = command.split_first().context("command is empty")?;
+            let mut child = tokio::process::Command::new(program)
+                .args(args)
+                .env("RULIADEMA_EVENT", format!("{:?}", alert.event))
+                .env("RULIADEMA_URL", &alert.url)
+                .env(
+                    "RULIADEMA_NAME",
+                    alert.name.as_deref().unwrap_or(&alert.url),
+                )
+                .env("RULIADEMA_STATUS", format!("{:?}", alert.status))
+                .env("RULIADEMA_SUMMARY", alert.summary())
+                .stdin(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| format!("failed to run {}", program))?;
+
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(&serde_json::to_vec(alert)?).await?;
+            }
+            let status = tokio::time::timeout(SEND_TIMEOUT, child.wait())
+                .await
+                .context("command timed out")??;
+            if !status.success() {
+                anyhow::bail!("{} exited with {}", program, status);
+            }
+        }
+    }
+    Ok(())
+}
+
+// 通知メール。from / to の ${ENV_NAME} は環境変数で置き換えてから解釈する
+fn email_message(alert: &Alert, from: &str, to: &[String]) -> anyhow::Result<Message> {
+    let mailbox = |field: &str, address: &str| {
+        let address = expand_env(address);
+        address
+            .parse::<Mailbox>()
+            .with_context(|| format!("{} {:?}", field, address))
+    };
+    let mut message = Message::builder()
+        .from(mailbox("from", from)?)
+        .subject(alert.summary());
+    for address in to {
+        message = message.to(mailbox("to", address)?);
+    }
+    Ok(message.body(serde_json::to_string_pretty(alert)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use std::convert::Infallible;
+
+    // 受け取ったリクエスト（X-Token ヘッダと JSON）
+    type Received = Arc<Mutex<Vec<(Option<String>, serde_json::Value)>>>;
+
+    // ローカルの webhook の代わり。statuses の順に応答し、尽きたら 200 を返す
+    fn stand_in(statuses: Vec<u16>) -> (String, Received) {
+        let received: Received = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(statuses.into_iter()));
+
+        let state = received.clone();
+        let make = make_service_fn(move |_| {
+            let received = state.clone();
+            let statuses = statuses.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let received = received.clone();
+                    let status = statuses.lock().unwrap().next().unwrap_or(200);
+                    async move {
+                        let token = req
+                            .headers()
+                            .get("x-token")
+                            .and_then(|v| v.to_str().ok())
+                            .map(String::from);
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let json = serde_json::from_slice(&body).unwrap_or_default();
+                        received.lock().unwrap().push((token, json));
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::empty())
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make);
+        let url = format!("http://{}/hook", server.local_addr());
+        tokio::spawn(server);
+        (url, received)
+    }
+
+    fn alerter(url: &str) -> Alerter {
+        let config: Config = toml::from_str(&format!(
+            r#"
+interval_seconds = 10
+timeout_seconds = 5
+max_concurrency = 1
+
+[notifiers.hook]
+type = "webhook"
+url = "{}"
+headers = {{ X-Token = "secret" }}
+
+[[targets]]
+name = "web"
+url = "https://example.com"
+notify = ["hook", "hook"]
+"#,
+            url
+        ))
+        .unwrap();
+        let mut alerter = Alerter::new(&config).unwrap();
+        alerter.retry = RetryPolicy {
+            retries: 2,
+            backoff_ms: 10,
+            max_backoff_ms: 10,
+        };
+        alerter
+    }
+
+    fn history() -> CheckHistory {
+        let mut history = CheckHistory::new("https://example.com".to_string(), 1000);
+        history.name = Some("web".to_string());
+        history
+    }
+
+    // 結果を積み、状態が変わったら通知して送り終わるのを待つ
+    async fn check(alerter: &Alerter, history: &mut CheckHistory, status: CheckStatus) {
+        let changes = history.push(CheckResult::new(status));
+        alerter.notify(history, &changes);
+        alerter.flush().await;
+    }
+
+    fn events(received: &Received) -> Vec<String> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, json)| json["event"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn webhook_receives_the_alert_json() {
+        let (url, received) = stand_in(vec![]);
+        let alerter = alerter(&url);
+        let mut history = history();
+
+        check(&alerter, &mut history, CheckStatus::Down).await;
+
+        let received = received.lock().unwrap();
+        // notify に2回書いた通知先にも1回だけ送る
+        assert_eq!(received.len(), 1);
+        let (token, json) = &received[0];
+        assert_eq!(token.as_deref(), Some("secret"));
+        assert_eq!(json["event"], "down");
+        assert_eq!(json["name"], "web");
+        assert_eq!(json["url"], "https://example.com");
+        assert_eq!(json["status"], "Down");
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let (url, received) = stand_in(vec![500, 503]);
+        let alerter = alerter(&url);
+        let mut history = history();
+
+        check(&alerter, &mut history, CheckStatus::Down).await;
+
+        assert_eq!(events(&received), ["down", "down", "down"]);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_the_limit_and_skip_client_errors() {
+        let (failing, failing_received) = stand_in(vec![500, 500, 500, 500]);
+        check(&alerter(&failing), &mut history(), CheckStatus::Down).await;
+        assert_eq!(failing_received.lock().unwrap().len(), 3);
+
+        let (rejecting, rejecting_received) = stand_in(vec![400]);
+        check(&alerter(&rejecting), &mut history(), CheckStatus::Down).await;
+        assert_eq!(rejecting_received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn the_same_change_is_sent_once() {
+        let (url, received) = stand_in(vec![]);
+        let alerter = alerter(&url);
+        let mut history = history();
+
+        check(&alerter, &mut history, CheckStatus::Down).await;
+        check(&alerter, &mut history, CheckStatus::Error).await;
+        // 状態を作り直しても（再起動・設定の読み直しなど）続けて同じ通知は送らない
+        history.state = Default::default();
+        check(&alerter, &mut history, CheckStatus::Down).await;
+        check(&alerter, &mut history, CheckStatus::Up).await;
+        check(&alerter, &mut history, CheckStatus::Down).await;
+
+        assert_eq!(events(&received), ["down", "recovered", "down"]);
+    }
+
+    #[test]
+    fn email_addresses_expand_env() {
+        // SAFETY: このテストだけが使う変数名
+        unsafe { std::env::set_var("RULIADEMA_TEST_ONCALL", "oncall@example.com") };
+        let mut history = history();
+        let changes = history.push(CheckResult::new(CheckStatus::Down));
+        let alert = Alert::new(changes[0], &history, history.results.back().unwrap());
+
+        let message = email_message(
+            &alert,
+            "ruliadema@example.com",
+            &[
+                "ops@example.com".to_string(),
+                "${RULIADEMA_TEST_ONCALL}".to_string(),
+            ],
+        )
+        .unwrap();
+        let to = message.headers().get_raw("To").unwrap();
+        assert!(to.contains("oncall@example.com"), "{}", to);
+
+        // 未定義の変数は空になり、アドレスとして読めない
+        let missing = email_message(&alert, "${RULIADEMA_TEST_UNDEFINED}", &[]);
+        assert!(missing.is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use clap::Parser;
 use crossterm::{
     ExecutableCommand,
     event::{self, KeyCode},
@@ -12,15 +13,141 @@ use ratatui::{
         Paragraph, Row, Table,
     },
 };
-use ruliadema::model::{CheckHistory, CheckStatus};
+use ruliadema::incident::{Incident, IncidentKind, IncidentLog};
+use ruliadema::model::{
+    CheckHistory, CheckKind, CheckResult, CheckStatus, LatencyPhase, MAX_HISTORY, Percentiles,
+};
+use ruliadema::paths::DataDir;
+use ruliadema::remote::{BREACH_LIMIT, Connection, Remote, breach_key};
+use ruliadema::report::{self, Availability, format_percent};
+use ruliadema::snapshot;
+use ruliadema::store::{self, Rollup, Store, TimeRange};
+use ruliadema::{Config, StorageConfig};
 use std::{
-    collections::BTreeMap,
-    fs::File,
-    io::{BufRead, BufReader, stdout},
-    time::Duration,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io::stdout,
+    path::PathBuf,
+    time::{Duration, Instant},
 };
 
+#[derive(Parser)]
+#[command(version, about = "TUI dashboard for the ruliadema daemon")]
+struct Cli {
+    /// Config file shown with 'c'
+    #[arg(short, long, env = "RULIADEMA_CONFIG", default_value = "config.toml")]
+    config: String,
+
+    /// Directory the daemon writes status.json and breaches.json to
+    #[arg(short, long, env = "RULIADEMA_DATA_DIR", default_value = ".")]
+    data_dir: PathBuf,
+
+    /// Read from a daemon's HTTP API instead of local files (e.g. http://host:9464)
+    #[arg(long, env = "RULIADEMA_REMOTE")]
+    remote: Option<String>,
+
+    /// Show only targets carrying this tag ('t' cycles through the tags)
+    #[arg(long)]
+    tag: Option<String>,
+}
+
+// 一覧の1行。group を持つターゲットがあれば、グループの見出しの下に並べる（None は group のないもの）
+enum Entry {
+    Group(Option<String>),
+    Target(String),
+}
+
+impl Entry {
+    fn target(&self) -> Option<&String> {
+        match self {
+            Entry::Target(key) => Some(key),
+            Entry::Group(_) => None,
+        }
+    }
+}
+
+// 1回の描画に使う内容（ローカルのファイルか daemon の API から）
+struct Data {
+    histories: BTreeMap<String, CheckHistory>,
+    incidents: Vec<Incident>,
+    breaches: Vec<serde_json::Value>, // --remote のときだけ（ローカルでは選択中のターゲット分を保存先から読む）
+    connection: Option<Connection>,   // --remote のときだけ
+    snapshot_error: Option<String>,   // status.json が読めないとき（ローカルのときだけ）
+}
+
+// 右側のグラフの期間（'r' で切り替える）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChartRange {
+    Recent, // status.json の直近の結果
+    Hour,
+    Day,
+    Week,
+}
+
+impl ChartRange {
+    fn next(self) -> Self {
+        match self {
+            ChartRange::Recent => ChartRange::Hour,
+            ChartRange::Hour => ChartRange::Day,
+            ChartRange::Day => ChartRange::Week,
+            ChartRange::Week => ChartRange::Recent,
+        }
+    }
+
+    // (期間, まとめる秒数)
+    fn window(self) -> Option<(chrono::Duration, u64)> {
+        match self {
+            ChartRange::Recent => None,
+            ChartRange::Hour => Some((chrono::Duration::hours(1), 60)),
+            ChartRange::Day => Some((chrono::Duration::days(1), 15 * 60)),
+            ChartRange::Week => Some((chrono::Duration::days(7), 3600)),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ChartRange::Recent => "recent",
+            ChartRange::Hour => "last 1h, per 1m",
+            ChartRange::Day => "last 24h, per 15m",
+            ChartRange::Week => "last 7d, per 1h",
+        }
+    }
+}
+
+// 直近の結果のグラフに描くもの（'p' で切り替える）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChartMode {
+    Total,
+    Phases,      // 区間ごとの積み上げ
+    Percentiles, // 各時点での窓の百分位
+}
+
+impl ChartMode {
+    fn next(self) -> Self {
+        match self {
+            ChartMode::Total => ChartMode::Phases,
+            ChartMode::Phases => ChartMode::Percentiles,
+            ChartMode::Percentiles => ChartMode::Total,
+        }
+    }
+}
+
+// 保存先（ruliadema.log / ruliadema.db）を読み直す間隔（稼働率は期間が長いので間隔を空ける）
+const STORE_REFRESH: Duration = Duration::from_secs(5);
+const REPORT_REFRESH: Duration = Duration::from_secs(30);
+
 fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let data_dir = DataDir::new(&cli.data_dir);
+    let remote = cli.remote.as_deref().map(Remote::connect).transpose()?;
+    // 設定が読めなければ既定の保存先（JSON Lines）を読む
+    let storage = Config::from_file(&cli.config)
+        .map(|config| config.storage)
+        .unwrap_or_else(|_| StorageConfig::default());
+    let store = match &remote {
+        Some(_) => None,
+        None => Some(store::open(&storage, &data_dir)?),
+    };
+
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
@@ -29,26 +156,129 @@ fn main() -> anyhow::Result<()> {
     list_state.select(Some(0));
 
     let mut show_config = false;
+    let mut config_title = cli.config.clone();
     let mut config_content = String::new();
 
     let mut show_breaches = false;
+    let mut show_incidents = false;
+    let mut chart_mode = ChartMode::Total;
+    let mut chart_range = ChartRange::Recent;
+    let mut hide_retired = false;
+    let mut tag_filter = cli.tag.clone();
+    let mut collapsed: HashSet<Option<String>> = HashSet::new();
     let mut scroll_offset: usize = 0;
 
+    // 選択中のターゲットの違反記録とまとめ（ローカルのとき、選択・期間が変わるか STORE_REFRESH ごとに読み直す）
+    let mut loaded: Option<(String, ChartRange, Instant)> = None;
+    let mut url_breaches: Vec<serde_json::Value> = Vec::new();
+    let mut rollups: Option<Vec<Rollup>> = None;
+    let mut reported: Option<(String, Instant)> = None;
+    let mut availability: Option<Vec<Availability>> = None;
+
     loop {
-        let histories: BTreeMap<String, CheckHistory> = if let Ok(file) = File::open("status.json")
-        {
-            serde_json::from_reader(file).unwrap_or_default()
-        } else {
-            BTreeMap::new()
+        let Data {
+            histories,
+            incidents: all_incidents,
+            breaches,
+            connection,
+            snapshot_error,
+        } = match &remote {
+            Some(remote) => {
+                let data = remote.snapshot();
+                Data {
+                    histories: data.histories,
+                    incidents: data.incidents,
+                    breaches: data.breaches,
+                    connection: Some(data.connection),
+                    snapshot_error: None,
+                }
+            }
+            None => load_local(&data_dir),
         };
 
-        let urls: Vec<String> = histories.keys().cloned().collect();
+        // 't' / --tag で選んだタグを持つものだけにし、設定から消えたターゲット（retired）は 'x' で隠せる
+        let tagged: Vec<&CheckHistory> = histories
+            .values()
+            .filter(|h| tag_filter.as_ref().is_none_or(|tag| h.has_tag(tag)))
+            .collect();
+        let visible: Vec<&CheckHistory> = tagged
+            .iter()
+            .copied()
+            .filter(|h| !(hide_retired && h.is_retired()))
+            .collect();
+        let hidden = tagged.len() - visible.len();
+        let entries = entries(&visible, &collapsed);
+
+        // 空の一覧を描画すると選択が外れるので、ターゲットが届いたら先頭を選び直す（--remote の接続直後など）
+        if list_state.selected().is_none() && !entries.is_empty() {
+            list_state.select(Some(0));
+        }
+
+        if let Some(selected) = list_state.selected()
+            && selected >= entries.len()
+            && !entries.is_empty()
+        {
+            list_state.select(Some(entries.len() - 1));
+        }
+        let selected_key = list_state
+            .selected()
+            .and_then(|i| entries.get(i))
+            .and_then(Entry::target);
+
+        // 選択中ターゲットの名前・グループとリクエスト定義の行数だけ Detail パネルを広げる
+        let target_info: Vec<String> = selected_key
+            .and_then(|key| histories.get(key))
+            .map(target_lines)
+            .unwrap_or_default();
+        let request_info: Vec<String> = selected_key
+            .and_then(|key| histories.get(key))
+            .map(request_lines)
+            .unwrap_or_default();
 
-        if let Some(selected) = list_state.selected() {
-            if selected >= urls.len() && !urls.is_empty() {
-                list_state.select(Some(urls.len() - 1));
+        if let Some(key) = selected_key {
+            match (&remote, &store) {
+                (Some(remote), _) => {
+                    url_breaches = breaches
+                        .iter()
+                        .filter(|v| breach_key(v) == Some(key.as_str()))
+                        .cloned()
+                        .collect();
+                    rollups = chart_range
+                        .window()
+                        .and_then(|(span, seconds)| remote.rollups(key, span, seconds));
+                    availability = remote.report(key);
+                }
+                (None, Some(store)) => {
+                    let fresh = loaded.as_ref().is_some_and(|(k, r, at)| {
+                        k == key && *r == chart_range && at.elapsed() < STORE_REFRESH
+                    });
+                    if !fresh {
+                        (url_breaches, rollups) = load_selected(store.as_ref(), key, chart_range);
+                        loaded = Some((key.clone(), chart_range, Instant::now()));
+                    }
+                    let fresh = reported
+                        .as_ref()
+                        .is_some_and(|(k, at)| k == key && at.elapsed() < REPORT_REFRESH);
+                    if !fresh && let Some(history) = histories.get(key) {
+                        let windows = report::DEFAULT_WINDOWS.map(String::from);
+                        availability = report::availability(
+                            store.as_ref(),
+                            key,
+                            history.slo_percent,
+                            &windows,
+                            chrono::Utc::now(),
+                        )
+                        .ok();
+                        reported = Some((key.clone(), Instant::now()));
+                    }
+                }
+                (None, None) => {}
             }
         }
+        let availability_info: Vec<String> = selected_key
+            .and_then(|key| histories.get(key))
+            .map(|history| availability_lines(history, availability.as_deref()))
+            .unwrap_or_default();
 
         terminal.draw(|frame| {
             let size = frame.area();
@@ -60,7 +290,7 @@ fn main() -> anyhow::Result<()> {
 
             if show_config {
                 let config_para = Paragraph::new(config_content.as_str())
-                    .block(Block::default().title(" config.toml ").borders(Borders::ALL))
+                    .block(Block::default().title(format!(" {} ", config_title)).borders(Borders::ALL))
                     .style(Style::default().fg(Color::Yellow));
 
                 frame.render_widget(config_para, main_chunks[0]);
@@ -72,34 +302,81 @@ fn main() -> anyhow::Result<()> {
 
                 let left_chunks = Layout::default()
                     .direction(Direction::Vertical)
-                    .constraints([Constraint::Min(0), Constraint::Length(12)])
+                    .constraints([Constraint::Min(0), Constraint::Length(18 + (target_info.len() + availability_info.len() + request_info.len()) as u16)])
                     .split(content_chunks[0]);
 
-                let items: Vec<ListItem> = urls
+                // 証明書を持つターゲットがあれば、名前の前に残り日数の列を出す
+                let show_cert_days = histories.values().any(|h| h.results.back().is_some_and(|r| r.cert_expires_at.is_some()));
+                let grouped = entries.iter().any(|e| matches!(e, Entry::Group(_)));
+
+                let items: Vec<ListItem> = entries
                     .iter()
-                    .map(|url| {
-                        let style = match histories.get(url).and_then(|h| h.results.back()) {
+                    .map(|entry| {
+                        let key = match entry {
+                            Entry::Target(key) => key,
+                            Entry::Group(group) => {
+                                // 見出し: 開閉の印、ターゲット数と Down の数
+                                let members: Vec<_> = visible.iter().filter(|h| h.group == *group).collect();
+                                let down = members.iter().filter(|h| !h.is_retired() && h.state.down).count();
+                                let mark = if collapsed.contains(group) { "▶" } else { "▼" };
+                                let mut label = format!("{} {} ({})", mark, group.as_deref().unwrap_or("Other"), members.len());
+                                let mut style = Style::default().add_modifier(Modifier::BOLD);
+                                if down > 0 {
+                                    label.push_str(&format!(" {} down", down));
+                                    style = style.fg(Color::Red);
+                                }
+                                return ListItem::new(label).style(style);
+                            }
+                        };
+                        let history = histories.get(key);
+                        let latest = history.and_then(|h| h.results.back());
+                        let state = history.map(|h| &h.state);
+                        let style = match latest {
+                            _ if history.is_some_and(|h| h.is_retired()) => Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+                            Some(_) if state.is_some_and(|s| s.flapping) => Style::default().fg(Color::Magenta),
+                            Some(_) if state.is_some_and(|s| s.down) => Style::default().fg(Color::Red),
                             Some(res) if matches!(res.status, CheckStatus::Up) => Style::default().fg(Color::Green),
-                            Some(_) => Style::default().fg(Color::Red),
+                            Some(res) if matches!(res.status, CheckStatus::Warning) => Style::default().fg(Color::Yellow),
+                            // 失敗したが fail_after 回に達していない
+                            Some(_) => Style::default().fg(Color::LightRed),
                             None => Style::default().fg(Color::DarkGray),
                         };
 
                         // オフセット分だけ文字をスキップ
-                        let display_url = if url.chars().count() > scroll_offset {
-                            url.chars().skip(scroll_offset).collect::<String>()
+                        let display_url = if key.chars().count() > scroll_offset {
+                            key.chars().skip(scroll_offset).collect::<String>()
                         } else {
                             String::new()
                         };
 
+                        let display_url = if show_cert_days {
+                            let days = latest.and_then(|r| r.cert_days_left()).map_or(String::new(), |d| format!("{}d", d));
+                            format!("{:>5} {}", days, display_url)
+                        } else {
+                            display_url
+                        };
+
+                        let display_url = if history.is_some_and(|h| h.is_retired()) {
+                            format!("{} (retired)", display_url)
+                        } else {
+                            display_url
+                        };
+                        let display_url = if grouped { format!("  {}", display_url) } else { display_url };
+
                         ListItem::new(display_url).style(style)
                     })
                     .collect();
 
-                let list_title = if scroll_offset > 0 {
-                    format!(" URLs (offset: {}) ", scroll_offset)
-                } else {
-                    " URLs ".to_string()
-                };
+                let mut list_title = " Targets ".to_string();
+                if let Some(tag) = &tag_filter {
+                    list_title.push_str(&format!("[tag: {}] ", tag));
+                }
+                if scroll_offset > 0 {
+                    list_title.push_str(&format!("(offset: {}) ", scroll_offset));
+                }
+                if hidden > 0 {
+                    list_title.push_str(&format!("({} retired hidden) ", hidden));
+                }
 
                 let list = List::new(items)
                     .block(Block::default().title(list_title).borders(Borders::ALL))
@@ -108,14 +385,16 @@ fn main() -> anyhow::Result<()> {
 
                 frame.render_stateful_widget(list, left_chunks[0], &mut list_state);
 
-                if let Some(selected_idx) = list_state.selected() {
-                    if let Some(url) = urls.get(selected_idx) {
-                        if let Some(history) = histories.get(url) {
+                if let Some(key) = selected_key
+                        && let Some(history) = histories.get(key) {
 
                             // --- Detail パネルの描画 ---
                             let latest = history.results.back();
                             let status_str = latest.map_or("N/A".to_string(), |r| format!("{:?}", r.status));
-                            let rt_str = latest.and_then(|r| r.response_time).map_or("N/A".to_string(), |d| format!("{} ms", d.as_millis()));
+                            let mut rt_str = latest.and_then(|r| r.response_time).map_or("N/A".to_string(), |d| format!("{} ms", d.as_millis()));
+                            if let Some(r) = latest && r.attempts > 1 {
+                                rt_str.push_str(&format!(" ({} attempts)", r.attempts));
+                            }
                             let diff_str = match latest.and_then(|r| r.diff_from_prev) {
                                 Some(d) if d > 0 => format!("+{} ms 🔺", d),
                                 Some(d) if d < 0 => format!("{} ms 🔽", d),
@@ -128,72 +407,213 @@ fn main() -> anyhow::Result<()> {
                                 None => "-".to_string(),
                             };
 
-                            let mut last_breach_ts = "--".to_string();
-                            if let Ok(file) = File::open("breaches.json") {
-                                let reader = BufReader::new(file);
-                                for line in reader.lines().filter_map(|l| l.ok()) {
-                                    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&line) {
-                                        // 該当URLのログが見つかるたびに上書きするので、最終的に「最後の日時」が残る
-                                        if v["url"].as_str() == Some(url.as_str()) {
-                                            if let Some(ts) = v["result"]["timestamp"].as_str() {
-                                                last_breach_ts = ts.to_string();
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                            // 該当URLの最後の記録の日時
+                            let last_breach_ts = url_breaches
+                                .last()
+                                .and_then(|v| v["result"]["timestamp"].as_str())
+                                .unwrap_or("--")
+                                .to_string();
 
-                            let detail_text = format!(
-                                " Target : {}\n\n Status        : {}\n Response time : {}\n\n Response diff : {}\n Limit         : {} ms\n Limit diff    : {}\n Last Breach   : {}",
-                                url, status_str, rt_str, diff_str, history.acceptable_latency_ms, diff_acc_str, last_breach_ts
-                            );
+                            let code_str = latest.and_then(|r| r.status_code).map_or("-".to_string(), |c| c.to_string());
+                            let reason_str = match latest {
+                                Some(r) if r.failure_reason.is_some() => r.failure_reason.clone().unwrap_or_default(),
+                                Some(r) => match (&r.error_kind, &r.error_message) {
+                                    (Some(kind), Some(message)) => format!("{:?}: {}", kind, message),
+                                    (Some(kind), None) => format!("{:?}", kind),
+                                    _ => "-".to_string(),
+                                },
+                                None => "-".to_string(),
+                            };
+
+                            let cert_str = match latest.and_then(|r| r.cert_expires_at.zip(r.cert_days_left())) {
+                                Some((expires_at, days)) => format!("expires {} ({} days)", expires_at.format("%Y-%m-%d"), days),
+                                None => "-".to_string(),
+                            };
+
+                            let state_str = state_label(history);
+
+                            // latency_sla があれば許容時間は百分位に対するもの
+                            let limit_str = match history.latency_sla {
+                                Some(sla) => format!("{}, {}", phase_label(history.latency_phase), sla.label()),
+                                None => phase_label(history.latency_phase).to_string(),
+                            };
+                            let percentile_str = history.state.percentiles.map_or("-".to_string(), |p| {
+                                format!("p50 {} p90 {} p95 {} p99 {} ms", p.p50, p.p90, p.p95, p.p99)
+                            });
+
+                            // 続いている障害があればそれを、なければ最後の障害を表示する
+                            let incidents: Vec<_> = all_incidents.iter().filter(|i| i.key() == key).collect();
+                            let incident_str = match incidents.last() {
+                                Some(i) if i.is_open() => format!("ONGOING for {} ({} checks)", format_duration(i.duration()), i.failed_checks),
+                                Some(i) => format!("{} ({})", local_time(i.started_at), format_duration(i.duration())),
+                                None => "--".to_string(),
+                            };
+
+                            let mut detail_text = format!(" Target : {}", key);
+                            for line in &target_info {
+                                detail_text.push('\n');
+                                detail_text.push_str(line);
+                            }
+                            detail_text.push_str(&format!(
+                                "\n\n Status        : {}\n State         : {}\n HTTP code     : {}\n Reason        : {}\n Response time : {}\n Certificate   : {}\n\n Response diff : {}\n Limit         : {} ms ({})\n Limit diff    : {}\n Percentiles   : {}\n Last Breach   : {}\n Last Incident : {}",
+                                status_str, state_str, code_str, reason_str, rt_str, cert_str, diff_str, history.acceptable_latency_ms, limit_str, diff_acc_str, percentile_str, last_breach_ts, incident_str
+                            ));
+                            for line in availability_info.iter().chain(&request_info) {
+                                detail_text.push('\n');
+                                detail_text.push_str(line);
+                            }
                             let detail_para = Paragraph::new(detail_text)
                                 .block(Block::default().title(" Detail ").borders(Borders::ALL));
 
                             frame.render_widget(detail_para, left_chunks[1]);
 
                             // --- 右側パネルの描画（モード切替） ---
-                            if show_breaches {
+                            if show_incidents {
+                                let rows: Vec<Row> = incidents
+                                    .iter()
+                                    .rev()
+                                    .map(|i| {
+                                        let style = if i.is_open() {
+                                            Style::default().fg(Color::Red)
+                                        } else {
+                                            Style::default().fg(Color::Gray)
+                                        };
+                                        let kind = match i.kind {
+                                            IncidentKind::Outage => "OUTAGE",
+                                            IncidentKind::SlaBreach => "SLA",
+                                        };
+                                        Row::new(vec![
+                                            Cell::from(local_time(i.started_at)),
+                                            Cell::from(i.ended_at.map_or("ongoing".to_string(), local_time)),
+                                            Cell::from(format_duration(i.duration())),
+                                            Cell::from(kind),
+                                            Cell::from(i.failed_checks.to_string()),
+                                            Cell::from(i.worst_latency_ms.map_or("-".to_string(), |ms| format!("{}ms", ms))),
+                                            Cell::from(i.first_error.clone().unwrap_or_else(|| "-".to_string())),
+                                        ]).style(style)
+                                    })
+                                    .collect();
+
+                                let table = Table::new(
+                                    rows,
+                                    [Constraint::Length(19), Constraint::Length(19), Constraint::Length(10), Constraint::Length(7), Constraint::Length(7), Constraint::Length(8), Constraint::Min(10)]
+                                )
+                                .header(Row::new(vec!["Start", "End", "Duration", "Kind", "Checks", "Worst", "First Error"]).style(Style::default().add_modifier(Modifier::BOLD)))
+                                .block(Block::default().title(format!(" Incidents: {} (Press 'i' to back) ", incidents.len())).borders(Borders::ALL));
+
+                                frame.render_widget(table, content_chunks[1]);
+
+                            } else if show_breaches {
                                 let mut breach_rows = Vec::new();
-                                if let Ok(file) = File::open("breaches.json") {
-                                    let reader = BufReader::new(file);
-                                    for line in reader.lines().filter_map(|l| l.ok()) {
-                                        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&line) {
-                                            if v["url"].as_str() == Some(url) {
-                                                let rt = v["response_time_ms"].as_u64().unwrap_or(0);
-                                                let limit = v["acceptable_latency_ms"].as_u64().unwrap_or(0);
-                                                let diff = v["diff_ms"].as_u64().unwrap_or(0);
-
-                                                let ts = v["result"]["timestamp"].as_str().unwrap_or("Unknown");
-
-                                                let style = if v["is_error"].as_bool().unwrap_or(false) {
-                                                    Style::default().fg(Color::Red)
-                                                } else {
-                                                    Style::default().fg(Color::Yellow)
-                                                };
-
-                                                breach_rows.push(Row::new(vec![
-                                                    Cell::from(ts.to_string()),
-                                                    Cell::from(if v["is_error"].as_bool().unwrap_or(false) { "ERROR".to_string() } else { format!("{}ms", rt) }),
-                                                    Cell::from(format!("{}ms", limit)),
-                                                    Cell::from(format!("+{}ms", diff)),
-                                                ]).style(style));
-                                            }
-                                        }
-                                    }
+                                for v in &url_breaches {
+                                    let rt = v["response_time_ms"].as_u64().unwrap_or(0);
+                                    let limit = v["acceptable_latency_ms"].as_u64().unwrap_or(0);
+                                    let diff = v["diff_ms"].as_u64().unwrap_or(0);
+
+                                    let ts = v["result"]["timestamp"].as_str().unwrap_or("Unknown");
+
+                                    let is_error = v["is_error"].as_bool().unwrap_or(false);
+                                    let is_failed = v["result"]["status"].as_str() == Some("AssertionFailed");
+                                    let is_cert = v["result"]["status"].as_str() == Some("Warning");
+
+                                    let style = if is_error || is_failed {
+                                        Style::default().fg(Color::Red)
+                                    } else {
+                                        Style::default().fg(Color::Yellow)
+                                    };
+
+                                    let response = if is_error {
+                                        "ERROR".to_string()
+                                    } else if is_failed {
+                                        "FAILED".to_string()
+                                    } else if is_cert {
+                                        "CERT".to_string()
+                                    } else {
+                                        format!("{}ms", rt)
+                                    };
+
+                                    let code = v["result"]["status_code"].as_u64().map_or("-".to_string(), |c| c.to_string());
+                                    let error = v["result"]["error_kind"].as_str()
+                                        .or(v["result"]["failure_reason"].as_str())
+                                        .unwrap_or("-");
+
+                                    breach_rows.push(Row::new(vec![
+                                        Cell::from(ts.to_string()),
+                                        Cell::from(response),
+                                        Cell::from(code),
+                                        Cell::from(format!("{}ms", limit)),
+                                        Cell::from(format!("+{}ms", diff)),
+                                        Cell::from(error.to_string()),
+                                    ]).style(style));
                                 }
 
                                 breach_rows.reverse();
 
                                 let table = Table::new(
                                     breach_rows,
-                                    [Constraint::Percentage(40), Constraint::Percentage(20), Constraint::Percentage(20), Constraint::Percentage(20)]
+                                    [Constraint::Percentage(30), Constraint::Percentage(12), Constraint::Percentage(8), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(26)]
                                 )
-                                .header(Row::new(vec!["Timestamp", "Response", "Limit", "Over"]).style(Style::default().add_modifier(Modifier::BOLD)))
+                                .header(Row::new(vec!["Timestamp", "Response", "Code", "Limit", "Over", "Error"]).style(Style::default().add_modifier(Modifier::BOLD)))
                                 .block(Block::default().title(" SLA Breaches History (Press 'b' to back) ").borders(Borders::ALL));
 
                                 frame.render_widget(table, content_chunks[1]);
 
+                            } else if let Some((span, _)) = chart_range.window() {
+                                // 保存先から読んだまとめ（区間ごとの平均と最大）
+                                let from = chrono::Utc::now() - span;
+                                let span_secs = span.num_seconds() as f64;
+                                let rollups = rollups.as_deref().unwrap_or_default();
+                                let points = |value: fn(&Rollup) -> Option<f64>| -> Vec<(f64, f64)> {
+                                    rollups
+                                        .iter()
+                                        .filter_map(|r| value(r).map(|v| ((r.start - from).num_seconds().max(0) as f64, v)))
+                                        .collect()
+                                };
+                                let avg_data = points(|r| r.avg_latency_ms());
+                                let max_data = points(|r| r.latency_max_ms.map(|ms| ms as f64));
+
+                                let acceptable_rt = history.acceptable_latency_ms as f64;
+                                let max_rt = max_data.iter().map(|&(_, v)| v).fold(acceptable_rt.max(100.0), f64::max);
+                                let acceptable_data: Vec<(f64, f64)> = vec![(0.0, acceptable_rt), (span_secs, acceptable_rt)];
+
+                                let datasets = vec![
+                                    Dataset::default()
+                                        .name("Max (ms)")
+                                        .marker(symbols::Marker::Braille)
+                                        .graph_type(GraphType::Line)
+                                        .style(Style::default().fg(Color::LightRed))
+                                        .data(&max_data),
+                                    Dataset::default()
+                                        .name("Average (ms)")
+                                        .marker(symbols::Marker::Braille)
+                                        .graph_type(GraphType::Line)
+                                        .style(Style::default().fg(Color::Cyan))
+                                        .data(&avg_data),
+                                    Dataset::default()
+                                        .name(format!("Limit ({} ms, {})", history.acceptable_latency_ms, phase_label(history.latency_phase)))
+                                        .marker(symbols::Marker::Dot)
+                                        .graph_type(GraphType::Line)
+                                        .style(Style::default().fg(Color::Yellow))
+                                        .data(&acceptable_data),
+                                ];
+
+                                let checks: u64 = rollups.iter().map(|r| r.checks).sum();
+                                let failures: u64 = rollups.iter().map(|r| r.failures).sum();
+                                let chart_title = format!(
+                                    " Latency, {}: {} checks, {} failed (Press 'r' for Range, 'b' for Breaches) ",
+                                    chart_range.label(), checks, failures
+                                );
+                                let chart = Chart::new(datasets)
+                                    .block(Block::default().title(chart_title).borders(Borders::ALL))
+                                    .x_axis(Axis::default().bounds([0.0, span_secs]).labels(vec![
+                                        Span::raw(local_time(from)), Span::raw("now"),
+                                    ]).style(Style::default().fg(Color::Gray)))
+                                    .y_axis(Axis::default().bounds([0.0, max_rt * 1.1]).labels(vec![
+                                        Span::raw("0"), Span::raw(format!("{}", max_rt as u64)),
+                                    ]).style(Style::default().fg(Color::Gray)));
+
+                                frame.render_widget(chart, content_chunks[1]);
+
                             } else {
                                 let mut chart_data: Vec<(f64, f64)> = vec![];
                                 let mut max_rt = 100.0;
@@ -205,6 +625,22 @@ fn main() -> anyhow::Result<()> {
                                     chart_data.push((i as f64, rt));
                                 }
 
+                                // 区間ごとの積み上げ（下から DNS → 接続 → TLS → TTFB → ボディ受信）
+                                let phase_data = if chart_mode == ChartMode::Phases { stacked_phases(history) } else { Vec::new() };
+                                if let Some(top) = phase_data.last() {
+                                    for &(_, v) in top {
+                                        if v > max_rt { max_rt = v; }
+                                    }
+                                }
+
+                                // p50 / p90 / p95 / p99 の推移
+                                let percentile_data = if chart_mode == ChartMode::Percentiles { rolling_percentiles(history) } else { Vec::new() };
+                                if let Some(top) = percentile_data.last() {
+                                    for &(_, v) in top {
+                                        if v > max_rt { max_rt = v; }
+                                    }
+                                }
+
                                 if acceptable_rt > max_rt { max_rt = acceptable_rt; }
 
                                 let acceptable_data: Vec<(f64, f64)> = vec![
@@ -212,23 +648,58 @@ fn main() -> anyhow::Result<()> {
                                     (history.results.len().saturating_sub(1) as f64, acceptable_rt),
                                 ];
 
-                                let datasets = vec![
-                                    Dataset::default()
-                                        .name("Response Time (ms)")
-                                        .marker(symbols::Marker::Braille)
-                                        .graph_type(GraphType::Line)
-                                        .style(Style::default().fg(Color::Cyan))
-                                        .data(&chart_data),
+                                let mut datasets = Vec::new();
+                                if chart_mode == ChartMode::Phases {
+                                    for ((name, color), data) in PHASES.iter().zip(&phase_data) {
+                                        datasets.push(
+                                            Dataset::default()
+                                                .name(*name)
+                                                .marker(symbols::Marker::Braille)
+                                                .graph_type(GraphType::Line)
+                                                .style(Style::default().fg(*color))
+                                                .data(data),
+                                        );
+                                    }
+                                } else if chart_mode == ChartMode::Percentiles {
+                                    for ((name, color), data) in PERCENTILES.iter().zip(&percentile_data) {
+                                        datasets.push(
+                                            Dataset::default()
+                                                .name(*name)
+                                                .marker(symbols::Marker::Braille)
+                                                .graph_type(GraphType::Line)
+                                                .style(Style::default().fg(*color))
+                                                .data(data),
+                                        );
+                                    }
+                                } else {
+                                    datasets.push(
+                                        Dataset::default()
+                                            .name("Response Time (ms)")
+                                            .marker(symbols::Marker::Braille)
+                                            .graph_type(GraphType::Line)
+                                            .style(Style::default().fg(Color::Cyan))
+                                            .data(&chart_data),
+                                    );
+                                }
+                                datasets.push(
                                     Dataset::default()
-                                        .name(format!("Limit ({} ms)", history.acceptable_latency_ms))
+                                        .name(format!("Limit ({} ms, {})", history.acceptable_latency_ms, phase_label(history.latency_phase)))
                                         .marker(symbols::Marker::Dot)
                                         .graph_type(GraphType::Line)
                                         .style(Style::default().fg(Color::Yellow))
                                         .data(&acceptable_data),
-                                ];
+                                );
 
+                                let chart_title = match chart_mode {
+                                    ChartMode::Total => " Latency History (Press 'p' for Phases, 'r' for Range, 'b' for Breaches) ".to_string(),
+                                    ChartMode::Phases => " Latency Phases, stacked (Press 'p' for Percentiles, 'r' for Range, 'b' for Breaches) ".to_string(),
+                                    ChartMode::Percentiles => format!(
+                                        " Latency Percentiles, {} (Press 'p' for Total, 'r' for Range, 'b' for Breaches) ",
+                                        window_label(history)
+                                    ),
+                                };
                                 let chart = Chart::new(datasets)
-                                    .block(Block::default().title(" Latency History (Press 'b' for Breaches) ").borders(Borders::ALL))
+                                    .block(Block::default().title(chart_title).borders(Borders::ALL))
                                     .x_axis(Axis::default().bounds([0.0, 50.0]).style(Style::default().fg(Color::Gray)))
                                     .y_axis(Axis::default().bounds([0.0, max_rt * 1.1]).labels(vec![
                                         Span::raw("0"), Span::raw(format!("{}", max_rt as u64)),
@@ -237,91 +708,132 @@ fn main() -> anyhow::Result<()> {
                                 frame.render_widget(chart, content_chunks[1]);
                             }
                         }
-                    }
-                }
             }
 
             let footer_text = if show_config {
                 " c/Esc: Back to Main   q: Quit "
-            } else if show_breaches {
-                " j/k: Select   h/l: Scroll URL   b: Show Graph   q: Quit "
+            } else if show_breaches || show_incidents {
+                " j/k: Select   h/l: Scroll   b: Breaches   i: Incidents   q: Quit "
             } else {
-                " j/k: Select   h/l: Scroll URL   b: Show Breaches   c: Config   q: Quit "
+                " j/k: Select   h/l: Scroll   Enter: Fold   t: Tag   b: Show Breaches   i: Incidents   p: Chart   r: Range   x: Retired   c: Config   q: Quit "
             };
-            let footer = Paragraph::new(footer_text)
-                .style(Style::default().fg(Color::DarkGray));
+            let mut footer_spans = Vec::new();
+            if let Some(connection) = &connection {
+                footer_spans.push(connection_span(connection));
+            }
+            if let Some(error) = &snapshot_error {
+                footer_spans.push(Span::styled(
+                    format!(" ✗ {} ", error),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            footer_spans.push(Span::styled(footer_text, Style::default().fg(Color::DarkGray)));
+            let footer = Paragraph::new(Line::from(footer_spans));
             frame.render_widget(footer, main_chunks[1]);
         })?;
 
         // --- キー入力処理 ---
-        if event::poll(Duration::from_millis(500))? {
-            if let event::Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('c') => {
-                        show_config = !show_config;
-                        if show_config {
-                            config_content =
-                                std::fs::read_to_string("config.toml").unwrap_or_else(|_| {
-                                    "Error: config.toml is missing or unreadable.".to_string()
-                                });
-                        }
+        if event::poll(Duration::from_millis(500))?
+            && let event::Event::Key(key) = event::read()?
+        {
+            match key.code {
+                KeyCode::Char('q') => break,
+                KeyCode::Char('c') => {
+                    show_config = !show_config;
+                    if show_config && let Some(remote) = &remote {
+                        let data = remote.snapshot();
+                        config_title = format!("{} ({})", data.config_path, remote.base);
+                        config_content = data.config;
+                    } else if show_config {
+                        config_content =
+                            std::fs::read_to_string(&cli.config).unwrap_or_else(|_| {
+                                format!("Error: {} is missing or unreadable.", cli.config)
+                            });
                     }
-                    KeyCode::Char('b') => {
-                        if !show_config {
-                            show_breaches = !show_breaches;
-                        }
+                }
+                KeyCode::Char('b') if !show_config => {
+                    show_breaches = !show_breaches;
+                    show_incidents = false;
+                }
+                KeyCode::Char('i') if !show_config => {
+                    show_incidents = !show_incidents;
+                    show_breaches = false;
+                }
+                KeyCode::Char('p') if !show_config => {
+                    chart_mode = chart_mode.next();
+                }
+                KeyCode::Char('r') if !show_config => {
+                    chart_range = chart_range.next();
+                }
+                KeyCode::Char('x') if !show_config => {
+                    hide_retired = !hide_retired;
+                }
+                // 次のタグで絞り込む（最後のタグの次は絞り込みなし）
+                KeyCode::Char('t') if !show_config => {
+                    let tags: BTreeSet<&String> =
+                        histories.values().flat_map(|h| &h.tags).collect();
+                    tag_filter = match &tag_filter {
+                        None => tags.first().map(|t| t.to_string()),
+                        Some(current) => tags
+                            .iter()
+                            .find(|t| t.as_str() > current.as_str())
+                            .map(|t| t.to_string()),
+                    };
+                    list_state.select(Some(0));
+                }
+                // グループの見出しで開閉する
+                KeyCode::Enter | KeyCode::Char(' ') if !show_config => {
+                    if let Some(Entry::Group(group)) =
+                        list_state.selected().and_then(|i| entries.get(i))
+                        && !collapsed.remove(group)
+                    {
+                        collapsed.insert(group.clone());
                     }
-                    KeyCode::Esc => {
-                        if show_config {
-                            show_config = false;
-                        }
-                        if show_breaches {
-                            show_breaches = false;
-                        }
+                }
+                KeyCode::Esc => {
+                    if show_config {
+                        show_config = false;
                     }
-                    KeyCode::Char('j') | KeyCode::Down => {
-                        if !show_config {
-                            let i = match list_state.selected() {
-                                Some(i) => {
-                                    if i >= urls.len().saturating_sub(1) {
-                                        0
-                                    } else {
-                                        i + 1
-                                    }
-                                }
-                                None => 0,
-                            };
-                            list_state.select(Some(i));
-                        }
+                    if show_breaches {
+                        show_breaches = false;
                     }
-                    KeyCode::Char('k') | KeyCode::Up => {
-                        if !show_config {
-                            let i = match list_state.selected() {
-                                Some(i) => {
-                                    if i == 0 {
-                                        urls.len().saturating_sub(1)
-                                    } else {
-                                        i - 1
-                                    }
-                                }
-                                None => 0,
-                            };
-                            list_state.select(Some(i));
-                        }
+                    if show_incidents {
+                        show_incidents = false;
                     }
-                    KeyCode::Char('l') | KeyCode::Right => {
-                        if !show_config {
-                            scroll_offset = scroll_offset.saturating_add(1);
+                }
+                KeyCode::Char('j') | KeyCode::Down if !show_config => {
+                    let i = match list_state.selected() {
+                        Some(i) => {
+                            if i >= entries.len().saturating_sub(1) {
+                                0
+                            } else {
+                                i + 1
+                            }
                         }
-                    }
-                    KeyCode::Char('h') | KeyCode::Left => {
-                        if !show_config {
-                            scroll_offset = scroll_offset.saturating_sub(1);
+                        None => 0,
+                    };
+                    list_state.select(Some(i));
+                }
+                KeyCode::Char('k') | KeyCode::Up if !show_config => {
+                    let i = match list_state.selected() {
+                        Some(i) => {
+                            if i == 0 {
+                                entries.len().saturating_sub(1)
+                            } else {
+                                i - 1
+                            }
                         }
-                    }
-                    _ => {}
+                        None => 0,
+                    };
+                    list_state.select(Some(i));
+                }
+                KeyCode::Char('l') | KeyCode::Right if !show_config => {
+                    scroll_offset = scroll_offset.saturating_add(1);
                 }
+                KeyCode::Char('h') | KeyCode::Left if !show_config => {
+                    scroll_offset = scroll_offset.saturating_sub(1);
+                }
+                _ => {}
             }
         }
     }
@@ -330,3 +842,314 @@ fn main() -> anyhow::Result<()> {
     stdout().execute(LeaveAlternateScreen)?;
     Ok(())
 }
+
+// data_dir の status.json / incidents.json を読む
+fn load_local(data_dir: &DataDir) -> Data {
+    let (histories, snapshot_error) = match snapshot::load(&data_dir.status()) {
+        Ok(histories) => (histories, None),
+        Err(e) => (BTreeMap::new(), Some(format!("{:#}", e))),
+    };
+
+    Data {
+        histories,
+        incidents: IncidentLog::read(&data_dir.incidents()),
+        breaches: Vec::new(),
+        connection: None,
+        snapshot_error,
+    }
+}
+
+// 一覧に並べる行。group を持つものがなければターゲットだけ、あればグループ名順（group のないものは最後）に見出しを付ける
+fn entries(histories: &[&CheckHistory], collapsed: &HashSet<Option<String>>) -> Vec<Entry> {
+    if histories.iter().all(|h| h.group.is_none()) {
+        return histories
+            .iter()
+            .map(|h| Entry::Target(h.key().to_string()))
+            .collect();
+    }
+    let mut groups: BTreeMap<Option<&String>, Vec<&str>> = BTreeMap::new();
+    for history in histories {
+        groups
+            .entry(history.group.as_ref())
+            .or_default()
+            .push(history.key());
+    }
+    // None は BTreeMap の先頭に来るので最後に回す
+    let ungrouped = groups.remove(&None);
+    let groups = groups.into_iter().chain(ungrouped.map(|keys| (None, keys)));
+
+    let mut entries = Vec::new();
+    for (group, keys) in groups {
+        let group = group.cloned();
+        let open = !collapsed.contains(&group);
+        entries.push(Entry::Group(group));
+        if open {
+            entries.extend(keys.into_iter().map(|key| Entry::Target(key.to_string())));
+        }
+    }
+    entries
+}
+
+// 選択中のターゲットの違反記録と、グラフの期間のまとめを保存先から読む
+fn load_selected(
+    store: &dyn Store,
+    key: &str,
+    chart_range: ChartRange,
+) -> (Vec<serde_json::Value>, Option<Vec<Rollup>>) {
+    let breaches = store
+        .breaches(
+            Some(key),
+            &TimeRange {
+                limit: Some(BREACH_LIMIT),
+                ..TimeRange::default()
+            },
+        )
+        .unwrap_or_default();
+    let rollups = chart_range.window().map(|(span, seconds)| {
+        store
+            .rollups(key, &TimeRange::last(span), seconds)
+            .unwrap_or_default()
+    });
+    (breaches, rollups)
+}
+
+// フッター左端の接続状態（--remote のとき）
+fn connection_span(connection: &Connection) -> Span<'static> {
+    match connection {
+        Connection::Connecting => {
+            Span::styled(" ◌ connecting ", Style::default().fg(Color::Yellow))
+        }
+        Connection::Connected { since } => Span::styled(
+            format!(" ● live since {} ", local_time(*since)),
+            Style::default().fg(Color::Green),
+        ),
+        Connection::Disconnected { error, retry_in } => Span::styled(
+            format!(
+                " ○ disconnected, retrying in {}s: {} ",
+                retry_in.as_secs(),
+                error
+            ),
+            Style::default().fg(Color::Red),
+        ),
+    }
+}
+
+// 日時をローカル時刻で表示する
+fn local_time(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+// "2h 05m" / "3m 20s" / "45s"
+fn format_duration(d: chrono::Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+// fail_after / recover_after でならした状態（"DOWN since 12:00:00 (3 failures)" など）
+fn state_label(history: &CheckHistory) -> String {
+    let state = &history.state;
+    let since = state
+        .since
+        .map(|at| {
+            format!(
+                " since {}",
+                at.with_timezone(&chrono::Local).format("%m-%d %H:%M:%S")
+            )
+        })
+        .unwrap_or_default();
+
+    if let Some(at) = history.retired_at {
+        format!(
+            "RETIRED since {} (not in the config)",
+            at.with_timezone(&chrono::Local).format("%m-%d %H:%M:%S")
+        )
+    } else if state.flapping {
+        format!("FLAPPING ({} changes)", state.flips.len())
+    } else if state.down {
+        format!("DOWN{} ({} failures)", since, state.consecutive_failures)
+    } else if state.sla_breached {
+        format!("SLA BREACH ({} slow)", state.consecutive_breaches)
+    } else if history.results.is_empty() {
+        "-".to_string()
+    } else {
+        format!("UP{}", since)
+    }
+}
+
+// name のあるターゲットの URL と、グループ・タグ（Detail の Target の下に出す）
+fn target_lines(history: &CheckHistory) -> Vec<String> {
+    let mut lines = Vec::new();
+    if history.name.is_some() {
+        lines.push(format!(" URL    : {}", history.url));
+    }
+    if let Some(group) = &history.group {
+        lines.push(format!(" Group  : {}", group));
+    }
+    if !history.tags.is_empty() {
+        lines.push(format!(" Tags   : {}", history.tags.join(", ")));
+    }
+    lines
+}
+
+// 既定の期間ごとの稼働率・SLA 達成率・error budget の残り（保存先から集計したもの、届く前は "-"）
+fn availability_lines(
+    history: &CheckHistory,
+    availability: Option<&[Availability]>,
+) -> Vec<String> {
+    let mut lines = vec![
+        String::new(),
+        format!(" Availability (SLO {}%)", history.slo_percent),
+        format!(
+            "   {:<7} {:>9} {:>9} {:>8}",
+            "Window", "Uptime", "SLA", "Budget"
+        ),
+    ];
+    for window in report::DEFAULT_WINDOWS {
+        let a = availability.and_then(|a| a.iter().find(|a| a.window == window));
+        lines.push(format!(
+            "   {:<7} {:>9} {:>9} {:>8}",
+            window,
+            format_percent(a.and_then(|a| a.uptime_percent), 3),
+            format_percent(a.and_then(|a| a.sla_percent), 3),
+            format_percent(a.and_then(|a| a.error_budget_remaining_percent), 1)
+        ));
+    }
+    lines
+}
+
+// Detail パネルに表示するリクエスト定義（メソッド・クエリ・ヘッダ・ボディ）
+fn request_lines(history: &CheckHistory) -> Vec<String> {
+    let kind = match history.kind {
+        CheckKind::Http => "http",
+        CheckKind::Tcp => "tcp",
+        CheckKind::Dns => "dns",
+        CheckKind::Tls => "tls",
+    };
+    if history.kind != CheckKind::Http {
+        return vec![String::new(), format!(" Type          : {}", kind)];
+    }
+
+    let spec = &history.request;
+    let method = if spec.method.is_empty() {
+        "GET"
+    } else {
+        spec.method.as_str()
+    };
+    let mut lines = vec![String::new(), format!(" Method        : {}", method)];
+
+    if !spec.query.is_empty() {
+        let query: Vec<String> = spec
+            .query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        lines.push(format!(" Query         : {}", query.join("&")));
+    }
+    for (name, value) in &spec.headers {
+        lines.push(format!(" Header        : {}: {}", name, value));
+    }
+    if let Some(body) = &spec.body {
+        let preview: String = body.chars().take(40).collect();
+        let ellipsis = if body.chars().count() > 40 { "…" } else { "" };
+        lines.push(format!(" Body          : {}{}", preview, ellipsis));
+    }
+    lines
+}
+
+// 積み上げグラフの凡例と色（下の区間から順に）
+const PHASES: [(&str, Color); 5] = [
+    ("DNS", Color::Magenta),
+    ("+ Connect", Color::Blue),
+    ("+ TLS", Color::Green),
+    ("+ TTFB", Color::Cyan),
+    ("+ Download", Color::White),
+];
+
+// 百分位のグラフの凡例と色
+const PERCENTILES: [(&str, Color); 4] = [
+    ("p50", Color::Green),
+    ("p90", Color::Cyan),
+    ("p95", Color::LightYellow),
+    ("p99", Color::LightRed),
+];
+
+// 百分位を取る窓（"last 20 checks" / "last 10 min"）
+fn window_label(history: &CheckHistory) -> String {
+    match history.latency_sla.and_then(|sla| sla.minutes) {
+        Some(minutes) => format!("last {} min", minutes),
+        None => format!(
+            "last {} checks",
+            history
+                .latency_sla
+                .and_then(|sla| sla.checks)
+                .unwrap_or(MAX_HISTORY)
+        ),
+    }
+}
+
+// 各チェックの時点での、それまでの窓（latency_sla、なければ直近 MAX_HISTORY 回）の百分位（PERCENTILES と同じ順）
+// status.json にある直近の結果だけから計算するので、窓がそれより長いと始めの方は短い窓になる
+fn rolling_percentiles(history: &CheckHistory) -> Vec<Vec<(f64, f64)>> {
+    let mut series = vec![Vec::new(); PERCENTILES.len()];
+    let sla = history.latency_sla;
+    let checks = match sla {
+        Some(sla) => sla.checks.unwrap_or(usize::MAX),
+        None => MAX_HISTORY,
+    };
+    let results: Vec<&CheckResult> = history.results.iter().collect();
+
+    for (i, result) in results.iter().enumerate() {
+        let from = sla
+            .and_then(|sla| sla.minutes)
+            .map(|m| result.timestamp - chrono::Duration::minutes(m as i64));
+        let samples = results[..=i]
+            .iter()
+            .rev()
+            .take_while(|r| from.is_none_or(|from| r.timestamp > from))
+            .filter(|r| !r.is_failing())
+            .filter_map(|r| r.latency(history.latency_phase))
+            .map(|d| d.as_millis() as u64)
+            .take(checks);
+        if let Some(p) = Percentiles::of(samples) {
+            for (j, v) in [p.p50, p.p90, p.p95, p.p99].into_iter().enumerate() {
+                series[j].push((i as f64, v as f64));
+            }
+        }
+    }
+    series
+}
+
+// 各チェックの区間所要時間を累積した系列（PHASES と同じ順）
+fn stacked_phases(history: &CheckHistory) -> Vec<Vec<(f64, f64)>> {
+    let mut series = vec![Vec::new(); PHASES.len()];
+
+    for (i, res) in history.results.iter().enumerate() {
+        let Some(t) = &res.timings else { continue };
+        let mut total = 0.0;
+        for (j, phase) in [t.dns, t.connect, t.tls, t.ttfb, t.download]
+            .iter()
+            .enumerate()
+        {
+            total += phase.map(|d| d.as_secs_f64() * 1000.0).unwrap_or(0.0);
+            series[j].push((i as f64, total));
+        }
+    }
+    series
+}
+
+fn phase_label(phase: LatencyPhase) -> &'static str {
+    match phase {
+        LatencyPhase::Total => "total",
+        LatencyPhase::Dns => "dns",
+        LatencyPhase::Connect => "connect",
+        LatencyPhase::Tls => "tls",
+        LatencyPhase::Ttfb => "ttfb",
+        LatencyPhase::Download => "download",
+    }
+}
@@ -1,49 +1,129 @@
-use chrono::Utc;
-use reqwest::Client;
-use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use url::Url;
 
-use crate::model::{CheckResult, CheckStatus};
+use crate::config::Target;
+use crate::model::{CertificateInfo, CheckKind, CheckResult, CheckStatus, ErrorKind, RetryPolicy};
+use crate::net::NetError;
 
-pub struct HttpChecker {
-    client: Client,
+mod dns;
+mod http;
+mod proxy;
+mod tcp;
+mod tls;
+
+pub use dns::DnsChecker;
+pub use http::HttpChecker;
+pub use tcp::TcpChecker;
+pub use tls::TlsChecker;
+
+// ターゲットの種類ごとの監視方法
+#[async_trait]
+pub trait Checker: Send + Sync {
+    async fn check_once(&self, target: &Target) -> CheckResult;
 }
 
-impl HttpChecker {
-    pub fn new(timeout_seconds: u64) -> anyhow::Result<Self> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(timeout_seconds))
-            .build()?;
+// type ごとの Checker をまとめたもの（daemon はここから振り分ける）
+pub struct Checkers {
+    http: HttpChecker,
+    tcp: TcpChecker,
+    dns: DnsChecker,
+    tls: TlsChecker,
+    retry: RetryPolicy,
+}
 
-        Ok(Self { client })
+impl Checkers {
+    pub fn new(timeout_seconds: u64, retry: RetryPolicy) -> anyhow::Result<Self> {
+        Ok(Self {
+            http: HttpChecker::new(timeout_seconds)?,
+            tcp: TcpChecker::new(timeout_seconds),
+            dns: DnsChecker::new(timeout_seconds),
+            tls: TlsChecker::new(timeout_seconds)?,
+            retry,
+        })
     }
 
-    pub async fn check_once(&self, url: &str) -> CheckResult {
-        let start = Instant::now();
-        let result = self.client.get(url).send().await;
-
-        match result {
-            Ok(resp) => {
-                let status = if resp.status().is_success() {
-                    CheckStatus::Up
-                } else {
-                    CheckStatus::Down
-                };
-
-                CheckResult {
-                    timestamp: Utc::now(),
-                    status,
-                    response_time: Some(start.elapsed()),
-                    diff_from_prev: None,
-                    diff_from_acceptable: None, // ▼ 追加: 初期値はNoneにしておく
-                }
-            }
-            Err(_) => CheckResult {
-                timestamp: Utc::now(),
-                status: CheckStatus::Error,
-                response_time: None,
-                diff_from_prev: None,
-                diff_from_acceptable: None, // ▼ 追加: エラー時も初期値はNone
-            },
+    pub fn get(&self, kind: CheckKind) -> &dyn Checker {
+        match kind {
+            CheckKind::Http => &self.http,
+            CheckKind::Tcp => &self.tcp,
+            CheckKind::Dns => &self.dns,
+            CheckKind::Tls => &self.tls,
         }
     }
+
+    // 失敗（Down / Error / AssertionFailed）のときは待ち時間を倍々にしながら retries 回まで試し直し、最後の結果を返す
+    pub async fn check_once(&self, target: &Target) -> CheckResult {
+        let checker = self.get(target.kind);
+        let policy = target.retry_policy(&self.retry);
+
+        let mut result = checker.check_once(target).await;
+        let mut attempts = 1;
+        while attempts <= policy.retries && result.is_failing() {
+            let delay = policy.delay(attempts);
+            log::debug!(
+                "{}: {:?}, retry {}/{} in {}ms",
+                target.url,
+                result.status,
+                attempts,
+                policy.retries,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+
+            result = checker.check_once(target).await;
+            attempts += 1;
+        }
+        result.attempts = attempts;
+        result
+    }
+}
+
+// "tcp://host:port" などからホスト名とポートを取り出す
+fn endpoint(target: &Target, default_port: Option<u16>) -> Result<(String, u16), NetError> {
+    let url = Url::parse(&target.url)
+        .map_err(|e| NetError::new(ErrorKind::Request, format!("{}: {}", target.url, e)))?;
+
+    let host = match url.host() {
+        Some(url::Host::Domain(domain)) => domain.to_string(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Ipv6(ip)) => ip.to_string(),
+        None => {
+            return Err(NetError::new(
+                ErrorKind::Request,
+                format!("{}: missing host", target.url),
+            ));
+        }
+    };
+    let port = url
+        .port_or_known_default()
+        .or(default_port)
+        .ok_or_else(|| {
+            NetError::new(ErrorKind::Request, format!("{}: missing port", target.url))
+        })?;
+
+    Ok((host, port))
+}
+
+// 受け取った証明書チェーンを結果に載せ、期限が近ければ Warning にする
+fn apply_certificates(
+    result: &mut CheckResult,
+    certificates: Vec<CertificateInfo>,
+    target: &Target,
+) {
+    let Some(earliest) = certificates.iter().min_by_key(|c| c.not_after) else {
+        return;
+    };
+    result.cert_expires_at = Some(earliest.not_after);
+
+    if let Some(days) = result.cert_days_left()
+        && days <= target.cert_warning_days
+        && matches!(result.status, CheckStatus::Up)
+    {
+        result.status = CheckStatus::Warning;
+        result.failure_reason = Some(format!(
+            "certificate expires in {} days ({})",
+            days, earliest.subject
+        ));
+    }
+    result.certificates = certificates;
 }
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::{Checker, endpoint};
+use crate::config::Target;
+use crate::model::{CheckResult, CheckStatus, ErrorKind, PhaseTimings};
+use crate::net::{self, NetError};
+
+// 名前解決ができ、期待したレコードが返ってくるかを見る
+pub struct DnsChecker {
+    timeout: Duration,
+}
+
+impl DnsChecker {
+    pub fn new(timeout_seconds: u64) -> Self {
+        Self {
+            timeout: Duration::from_secs(timeout_seconds),
+        }
+    }
+
+    // 監視なのでキャッシュは使わず、毎回問い合わせる
+    fn resolver(
+        &self,
+        nameserver: Option<&str>,
+        timeout: Duration,
+    ) -> Result<TokioAsyncResolver, NetError> {
+        let (config, mut opts) = match nameserver {
+            Some(ns) => {
+                let addr = parse_nameserver(ns).ok_or_else(|| {
+                    NetError::new(ErrorKind::Request, format!("invalid nameserver {:?}", ns))
+                })?;
+                let servers =
+                    NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+                (
+                    ResolverConfig::from_parts(None, vec![], servers),
+                    ResolverOpts::default(),
+                )
+            }
+            None => hickory_resolver::system_conf::read_system_conf()
+                .map_err(|e| NetError::new(ErrorKind::Dns, e.to_string()))?,
+        };
+        opts.cache_size = 0;
+        opts.timeout = timeout;
+        opts.attempts = 1;
+
+        Ok(TokioAsyncResolver::tokio(config, opts))
+    }
+}
+
+#[async_trait]
+impl Checker for DnsChecker {
+    async fn check_once(&self, target: &Target) -> CheckResult {
+        let timeout = target.timeout(self.timeout);
+        let options = target.dns.clone().unwrap_or_default();
+        let start = Instant::now();
+
+        let answers = tokio::time::timeout(timeout, async {
+            let (name, _) = endpoint(target, Some(53))?;
+            let record_type = RecordType::from_str(&options.record_type.to_uppercase())
+                .map_err(|e| NetError::new(ErrorKind::Request, e.to_string()))?;
+            let resolver = self.resolver(options.nameserver.as_deref(), timeout)?;
+
+            let lookup = resolver
+                .lookup(name.as_str(), record_type)
+                .await
+                .map_err(|e| match e.kind() {
+                    ResolveErrorKind::NoRecordsFound { response_code, .. } => NetError::new(
+                        ErrorKind::Dns,
+                        format!("{} {}: no records ({})", name, record_type, response_code),
+                    ),
+                    _ => NetError::new(net::resolve_error_kind(&e), format!("{}: {}", name, e)),
+                })?;
+
+            Ok::<_, NetError>(lookup.iter().map(format_rdata).collect::<Vec<_>>())
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(NetError::new(
+                ErrorKind::Timeout,
+                format!("no answer within {}s", timeout.as_secs()),
+            ))
+        });
+
+        let elapsed = start.elapsed();
+        let timings = PhaseTimings {
+            dns: Some(elapsed),
+            ..PhaseTimings::default()
+        };
+
+        let answers = match answers {
+            Ok(answers) => answers,
+            Err(e) => {
+                return CheckResult {
+                    timings: Some(timings),
+                    ..CheckResult::from(e)
+                };
+            }
+        };
+
+        // 期待したレコードがすべて応答に含まれているか（大文字小文字・末尾のドットは無視）
+        let found: Vec<String> = answers.iter().map(|a| normalize(a)).collect();
+        let missing: Vec<&String> = options
+            .expected
+            .iter()
+            .filter(|e| !found.contains(&normalize(e)))
+            .collect();
+
+        let mut result = CheckResult {
+            response_time: Some(elapsed),
+            timings: Some(timings),
+            ..CheckResult::new(CheckStatus::Up)
+        };
+        if !missing.is_empty() {
+            result.status = CheckStatus::AssertionFailed;
+            result.failure_reason = Some(format!("missing {:?} (got {:?})", missing, answers));
+        }
+        result
+    }
+}
+
+fn parse_nameserver(ns: &str) -> Option<SocketAddr> {
+    SocketAddr::from_str(ns)
+        .ok()
+        .or_else(|| IpAddr::from_str(ns).ok().map(|ip| SocketAddr::new(ip, 53)))
+}
+
+fn format_rdata(rdata: &RData) -> String {
+    match rdata {
+        RData::TXT(txt) => txt
+            .iter()
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .collect::<Vec<_>>()
+            .join(""),
+        other => other.to_string(),
+    }
+}
+
+fn normalize(record: &str) -> String {
+    record.trim().trim_end_matches('.').to_lowercase()
+}
@@ -0,0 +1,534 @@
+use async_trait::async_trait;
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::HashSet;
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls;
+use url::Url;
+
+use super::proxy::{self, Proxies};
+use super::{Checker, apply_certificates};
+use crate::config::{Assertions, Target, expand_env};
+use crate::model::{CertificateInfo, CheckResult, CheckStatus, ErrorKind, PhaseTimings};
+use crate::net::{self, NetError};
+
+// reqwest と同じくリダイレクトは最大10回まで追う
+const MAX_REDIRECTS: usize = 10;
+
+pub struct HttpChecker {
+    timeout: Duration,
+    tls: TlsConnector,
+    proxies: Proxies,
+}
+
+// 送信するリクエストの材料（リダイレクトのたびに組み立て直す）
+struct PreparedRequest {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+}
+
+// ボディまで受信し終えたレスポンス
+struct Fetched {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    truncated: bool, // max_body_bytes を超えて読み込みを打ち切った
+    response_time: Duration,
+}
+
+impl HttpChecker {
+    pub fn new(timeout_seconds: u64) -> anyhow::Result<Self> {
+        let mut config = net::tls_config()?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Self {
+            timeout: Duration::from_secs(timeout_seconds),
+            tls: TlsConnector::from(Arc::new(config)),
+            proxies: Proxies::from_env(),
+        })
+    }
+}
+
+#[async_trait]
+impl Checker for HttpChecker {
+    async fn check_once(&self, target: &Target) -> CheckResult {
+        let timeout = target.timeout(self.timeout);
+        let start = Instant::now();
+        let mut timings = PhaseTimings::default();
+        let mut certificates = Vec::new();
+
+        let fetched = match tokio::time::timeout(
+            timeout,
+            self.fetch(target, start, &mut timings, &mut certificates),
+        )
+        .await
+        {
+            Ok(fetched) => fetched,
+            Err(_) => Err(NetError::new(
+                ErrorKind::Timeout,
+                format!("no complete response within {}s", timeout.as_secs()),
+            )),
+        };
+
+        let mut result = CheckResult {
+            timings: Some(timings),
+            ..CheckResult::new(CheckStatus::Error)
+        };
+
+        match fetched {
+            Ok(resp) => {
+                result.response_time = Some(resp.response_time);
+                result.status_code = Some(resp.status.as_u16());
+
+                result.status = match &target.assertions {
+                    Some(assertions) => match verify(assertions, &resp) {
+                        Ok(()) => CheckStatus::Up,
+                        Err(Failure::Down) => CheckStatus::Down,
+                        Err(Failure::Assertion(reason)) => {
+                            result.failure_reason = Some(reason);
+                            CheckStatus::AssertionFailed
+                        }
+                    },
+                    None if resp.status.is_success() => CheckStatus::Up,
+                    None => CheckStatus::Down,
+                };
+            }
+            Err(e) => {
+                result.error_kind = Some(e.kind);
+                result.error_message = Some(e.message);
+            }
+        }
+
+        apply_certificates(&mut result, certificates, target);
+        result
+    }
+}
+
+impl HttpChecker {
+    // リダイレクトを追いながらリクエストを送り、ボディまで受信する
+    async fn fetch(
+        &self,
+        target: &Target,
+        start: Instant,
+        timings: &mut PhaseTimings,
+        certificates: &mut Vec<CertificateInfo>,
+    ) -> Result<Fetched, NetError> {
+        let mut req = prepare(target)?;
+        let mut visited = HashSet::new();
+
+        let mut resp = loop {
+            visited.insert(req.url.clone());
+            let resp = self.send(&req, timings, certificates).await?;
+
+            let location = resp
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok());
+            let (true, Some(location)) = (resp.status().is_redirection(), location) else {
+                break resp;
+            };
+
+            if visited.len() > MAX_REDIRECTS {
+                return Err(NetError::new(
+                    ErrorKind::RedirectLoop,
+                    format!("too many redirects (> {})", MAX_REDIRECTS),
+                ));
+            }
+            let next = req.url.join(location).map_err(|e| {
+                NetError::new(
+                    ErrorKind::Request,
+                    format!("bad Location {:?}: {}", location, e),
+                )
+            })?;
+            if visited.contains(&next) {
+                return Err(NetError::new(
+                    ErrorKind::RedirectLoop,
+                    format!("redirect loop at {}", next),
+                ));
+            }
+
+            // 301/302/303 はボディを捨てて GET に切り替える（ブラウザ・reqwest と同じ挙動）
+            if matches!(resp.status().as_u16(), 301..=303) && req.method != Method::HEAD {
+                req.method = Method::GET;
+                req.body = None;
+                req.headers.remove(header::CONTENT_TYPE);
+            }
+            // 別のホストへ移るときは認証情報を送らない（reqwest と同じ）。Host も元のホスト用なので外す
+            if (next.scheme(), next.host_str(), next.port_or_known_default())
+                != (
+                    req.url.scheme(),
+                    req.url.host_str(),
+                    req.url.port_or_known_default(),
+                )
+            {
+                for name in [
+                    header::AUTHORIZATION,
+                    header::COOKIE,
+                    header::PROXY_AUTHORIZATION,
+                    header::HOST,
+                ] {
+                    req.headers.remove(name);
+                }
+            }
+            req.url = next;
+        };
+        let response_time = start.elapsed();
+
+        // 検証にボディが要らない場合も、受信時間の計測のため最後まで読む
+        let (keep, limit) = match &target.assertions {
+            Some(a) => (a.needs_body(), a.max_body_bytes),
+            None => (false, None),
+        };
+        let download_start = Instant::now();
+        let mut body = Vec::new();
+        let mut received: u64 = 0;
+        let mut truncated = false;
+
+        while let Some(chunk) = resp.body_mut().data().await {
+            let chunk = chunk.map_err(|e| NetError::new(ErrorKind::BodyRead, error_chain(&e)))?;
+            received += chunk.len() as u64;
+            if keep {
+                body.extend_from_slice(&chunk);
+            }
+            // 上限を超えた時点で読み込みを打ち切る
+            if let Some(max) = limit
+                && received > max
+            {
+                truncated = true;
+                break;
+            }
+        }
+        timings.download = Some(download_start.elapsed());
+
+        Ok(Fetched {
+            status: resp.status(),
+            headers: resp.headers().clone(),
+            body,
+            truncated,
+            response_time,
+        })
+    }
+
+    // 1ホップ分: 名前解決 → 接続 → (TLS) → リクエスト送信 → ヘッダ受信
+    // https の場合は最後に接続したサーバの証明書チェーンを certificates に残す
+    // プロキシを使う場合は名前解決・接続の相手がプロキシになり、https は CONNECT で張ったトンネル越しに TLS を話す
+    async fn send(
+        &self,
+        req: &PreparedRequest,
+        timings: &mut PhaseTimings,
+        certificates: &mut Vec<CertificateInfo>,
+    ) -> Result<Response<Body>, NetError> {
+        let host = match req.url.host() {
+            Some(url::Host::Domain(domain)) => domain.to_string(),
+            Some(url::Host::Ipv4(ip)) => ip.to_string(),
+            Some(url::Host::Ipv6(ip)) => ip.to_string(),
+            None => {
+                return Err(NetError::new(
+                    ErrorKind::Request,
+                    format!("{}: missing host", req.url),
+                ));
+            }
+        };
+        let port = req.url.port_or_known_default().ok_or_else(|| {
+            NetError::new(ErrorKind::Request, format!("{}: unknown port", req.url))
+        })?;
+
+        let proxy = self.proxies.for_url(&req.url);
+        let (peer_host, peer_port) = match proxy {
+            Some(proxy) => match (
+                proxy.scheme(),
+                proxy.host_str(),
+                proxy.port_or_known_default(),
+            ) {
+                ("http", Some(host), Some(port)) => {
+                    (host.trim_matches(['[', ']']).to_string(), port)
+                }
+                _ => {
+                    return Err(NetError::new(
+                        ErrorKind::Request,
+                        format!("unsupported proxy {}", proxy),
+                    ));
+                }
+            },
+            None => (host.clone(), port),
+        };
+        let proxy_auth = proxy.and_then(proxy::authorization);
+
+        let (addrs, dns) = net::resolve(&peer_host, peer_port).await?;
+        add(&mut timings.dns, dns);
+        let (mut stream, connect) = net::connect(&addrs).await?;
+        add(&mut timings.connect, connect);
+
+        match req.url.scheme() {
+            "http" => {
+                // プロキシには絶対 URI で送る
+                let request = build_request(req, proxy.is_some(), proxy_auth.as_ref())?;
+                exchange(stream, request, timings).await
+            }
+            "https" => {
+                if proxy.is_some() {
+                    let start = Instant::now();
+                    proxy::tunnel(&mut stream, &host, port, proxy_auth.as_ref()).await?;
+                    add(&mut timings.connect, start.elapsed());
+                }
+                let request = build_request(req, false, None)?;
+                let (stream, handshake) = net::tls_handshake(&self.tls, stream, &host).await?;
+                add(&mut timings.tls, handshake);
+                *certificates = net::peer_certificates(&stream);
+                exchange(stream, request, timings).await
+            }
+            scheme => Err(NetError::new(
+                ErrorKind::Request,
+                format!("unsupported scheme {:?}", scheme),
+            )),
+        }
+    }
+}
+
+// Target の定義（メソッド・ヘッダ・クエリ・ボディ）からリクエストの材料を作る
+fn prepare(target: &Target) -> Result<PreparedRequest, NetError> {
+    let invalid = |e: &dyn std::fmt::Display| NetError::new(ErrorKind::Request, e.to_string());
+
+    let method =
+        Method::from_bytes(target.method.to_uppercase().as_bytes()).map_err(|e| invalid(&e))?;
+    let mut url =
+        Url::parse(&target.url).map_err(|e| invalid(&format!("{}: {}", target.url, e)))?;
+
+    if !target.query.is_empty() {
+        let mut pairs = url.query_pairs_mut();
+        for (k, v) in &target.query {
+            pairs.append_pair(k, &expand_env(v));
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &target.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+        let value = HeaderValue::from_str(&expand_env(value)).map_err(|e| invalid(&e))?;
+        headers.insert(name, value);
+    }
+
+    let body = if let Some(json) = &target.json {
+        if !headers.contains_key(header::CONTENT_TYPE) {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+        }
+        Some(json.to_string().into_bytes())
+    } else {
+        target.body.as_ref().map(|b| expand_env(b).into_bytes())
+    };
+
+    Ok(PreparedRequest {
+        method,
+        url,
+        headers,
+        body,
+    })
+}
+
+// http をプロキシ経由で送るときは via_proxy（絶対 URI で送る）と Proxy-Authorization の値を渡す
+fn build_request(
+    req: &PreparedRequest,
+    via_proxy: bool,
+    proxy_auth: Option<&HeaderValue>,
+) -> Result<Request<Body>, NetError> {
+    let uri = if via_proxy {
+        let mut url = req.url.clone();
+        url.set_fragment(None);
+        url.to_string()
+    } else {
+        let mut path = req.url.path().to_string();
+        if let Some(query) = req.url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        path
+    };
+
+    let mut builder = Request::builder()
+        .method(req.method.clone())
+        .uri(uri)
+        .header(header::CONNECTION, "close");
+    // headers で Host を指定していればそちらを使う
+    if !req.headers.contains_key(header::HOST) {
+        let host = match (req.url.host_str(), req.url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => String::new(),
+        };
+        builder = builder.header(header::HOST, host);
+    }
+    if let Some(auth) = proxy_auth {
+        builder = builder.header(header::PROXY_AUTHORIZATION, auth);
+    }
+    for (name, value) in &req.headers {
+        builder = builder.header(name, value);
+    }
+
+    let body = match &req.body {
+        Some(bytes) => Body::from(bytes.clone()),
+        None => Body::empty(),
+    };
+    builder
+        .body(body)
+        .map_err(|e| NetError::new(ErrorKind::Request, e.to_string()))
+}
+
+// 確立済みの接続で HTTP/1.1 のリクエストを1回だけ送る
+async fn exchange<S>(
+    stream: S,
+    request: Request<Body>,
+    timings: &mut PhaseTimings,
+) -> Result<Response<Body>, NetError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let sent = Instant::now();
+    let (mut sender, conn) = hyper::client::conn::handshake(stream)
+        .await
+        .map_err(|e| NetError::new(ErrorKind::Connect, error_chain(&e)))?;
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let resp = sender
+        .send_request(request)
+        .await
+        .map_err(|e| NetError::new(classify(&e), error_chain(&e)))?;
+    add(&mut timings.ttfb, sent.elapsed());
+    Ok(resp)
+}
+
+// 送受信中のエラーを原因の連鎖から型で分類する（TLS の接続なら rustls のエラーが入っている）
+fn classify(err: &hyper::Error) -> ErrorKind {
+    let mut source: Option<&(dyn StdError + 'static)> = Some(err);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            if io
+                .get_ref()
+                .is_some_and(|inner| inner.is::<rustls::Error>())
+            {
+                return ErrorKind::Tls;
+            }
+            return match io.kind() {
+                std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+                _ => ErrorKind::Connect,
+            };
+        }
+        source = e.source();
+    }
+    ErrorKind::Other
+}
+
+fn add(slot: &mut Option<Duration>, elapsed: Duration) {
+    *slot = Some(slot.unwrap_or_default() + elapsed);
+}
+
+// "connection closed before message completed: ..." のように原因を連結する
+fn error_chain(err: &(dyn StdError + 'static)) -> String {
+    let mut parts = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(e) = source {
+        let text = e.to_string();
+        if !parts.iter().any(|p| p.contains(&text)) {
+            parts.push(text);
+        }
+        source = e.source();
+    }
+    parts.join(": ")
+}
+
+enum Failure {
+    // 許可リスト未指定で 2xx 以外（従来どおり Down 扱い）
+    Down,
+    Assertion(String),
+}
+
+// [targets.assert] の条件を上から順に検証し、最初に失敗した理由を返す
+fn verify(assertions: &Assertions, resp: &Fetched) -> Result<(), Failure> {
+    let code = resp.status.as_u16();
+    if assertions.status.is_empty() {
+        if !resp.status.is_success() {
+            return Err(Failure::Down);
+        }
+    } else if !assertions.status.iter().any(|r| r.contains(code)) {
+        let allowed: Vec<String> = assertions.status.iter().map(|r| r.to_string()).collect();
+        return Err(Failure::Assertion(format!(
+            "status {} not in [{}]",
+            code,
+            allowed.join(", ")
+        )));
+    }
+
+    for name in &assertions.required_headers {
+        if !resp.headers.contains_key(name.as_str()) {
+            return Err(Failure::Assertion(format!("missing header {}", name)));
+        }
+    }
+
+    if resp.truncated
+        && let Some(max) = assertions.max_body_bytes
+    {
+        return Err(Failure::Assertion(format!("body exceeds {} bytes", max)));
+    }
+    let text = String::from_utf8_lossy(&resp.body);
+
+    for needle in &assertions.body_contains {
+        if !text.contains(needle.as_str()) {
+            return Err(Failure::Assertion(format!(
+                "body does not contain {:?}",
+                needle
+            )));
+        }
+    }
+
+    // パターンは読み込み時にコンパイル済み（不正なものは validate() で弾かれる）
+    if let Some(pattern) = &assertions.body_regex {
+        let re = pattern
+            .get()
+            .map_err(|e| Failure::Assertion(format!("invalid body_regex: {}", e)))?;
+        if !re.is_match(&text) {
+            return Err(Failure::Assertion(format!(
+                "body does not match /{}/",
+                pattern
+            )));
+        }
+    }
+
+    if !assertions.json_path.is_empty() {
+        let value: serde_json::Value = serde_json::from_slice(&resp.body)
+            .map_err(|e| Failure::Assertion(format!("body is not JSON: {}", e)))?;
+
+        for check in &assertions.json_path {
+            let path = check.path.get().map_err(|e| {
+                Failure::Assertion(format!("invalid json_path {}: {}", check.path, e))
+            })?;
+            match path.query(&value).exactly_one() {
+                Ok(actual) if *actual == check.equals => {}
+                Ok(actual) => {
+                    return Err(Failure::Assertion(format!(
+                        "{} = {} (expected {})",
+                        check.path, actual, check.equals
+                    )));
+                }
+                Err(_) => {
+                    return Err(Failure::Assertion(format!(
+                        "{} did not match exactly one value",
+                        check.path
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+use super::{Checker, endpoint};
+use crate::config::Target;
+use crate::model::{CheckResult, CheckStatus, ErrorKind, PhaseTimings};
+use crate::net::{self, NetError};
+
+// ポートに TCP 接続できるかだけを見る（DB やメールサーバ向け）
+pub struct TcpChecker {
+    timeout: Duration,
+}
+
+impl TcpChecker {
+    pub fn new(timeout_seconds: u64) -> Self {
+        Self {
+            timeout: Duration::from_secs(timeout_seconds),
+        }
+    }
+}
+
+#[async_trait]
+impl Checker for TcpChecker {
+    async fn check_once(&self, target: &Target) -> CheckResult {
+        let timeout = target.timeout(self.timeout);
+        let start = Instant::now();
+        let mut timings = PhaseTimings::default();
+
+        let connected = tokio::time::timeout(timeout, async {
+            let (host, port) = endpoint(target, None)?;
+            let (addrs, dns) = net::resolve(&host, port).await?;
+            timings.dns = Some(dns);
+            let (_stream, connect) = net::connect(&addrs).await?;
+            timings.connect = Some(connect);
+            Ok::<_, NetError>(())
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(NetError::new(
+                ErrorKind::Timeout,
+                format!("no connection within {}s", timeout.as_secs()),
+            ))
+        });
+
+        let result = match connected {
+            Ok(()) => CheckResult {
+                response_time: Some(start.elapsed()),
+                ..CheckResult::new(CheckStatus::Up)
+            },
+            Err(e) => CheckResult::from(e),
+        };
+        CheckResult {
+            timings: Some(timings),
+            ..result
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_rustls::TlsConnector;
+
+use super::{Checker, apply_certificates, endpoint};
+use crate::config::Target;
+use crate::model::{CheckResult, CheckStatus, ErrorKind, PhaseTimings};
+use crate::net::{self, NetError};
+
+// TLS ハンドシェイクと証明書の検証だけを行う（HTTP は話さない）
+pub struct TlsChecker {
+    timeout: Duration,
+    tls: TlsConnector,
+}
+
+impl TlsChecker {
+    pub fn new(timeout_seconds: u64) -> anyhow::Result<Self> {
+        Ok(Self {
+            timeout: Duration::from_secs(timeout_seconds),
+            tls: TlsConnector::from(Arc::new(net::tls_config()?)),
+        })
+    }
+}
+
+#[async_trait]
+impl Checker for TlsChecker {
+    async fn check_once(&self, target: &Target) -> CheckResult {
+        let timeout = target.timeout(self.timeout);
+        let start = Instant::now();
+        let mut timings = PhaseTimings::default();
+        let mut certificates = Vec::new();
+
+        let handshaked = tokio::time::timeout(timeout, async {
+            let (host, port) = endpoint(target, Some(443))?;
+            let (addrs, dns) = net::resolve(&host, port).await?;
+            timings.dns = Some(dns);
+            let (stream, connect) = net::connect(&addrs).await?;
+            timings.connect = Some(connect);
+            let (stream, handshake) = net::tls_handshake(&self.tls, stream, &host).await?;
+            timings.tls = Some(handshake);
+            certificates = net::peer_certificates(&stream);
+            Ok::<_, NetError>(())
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(NetError::new(
+                ErrorKind::Timeout,
+                format!("no handshake within {}s", timeout.as_secs()),
+            ))
+        });
+
+        let mut result = match handshaked {
+            Ok(()) => CheckResult {
+                response_time: Some(start.elapsed()),
+                ..CheckResult::new(CheckStatus::Up)
+            },
+            Err(e) => CheckResult::from(e),
+        };
+        apply_certificates(&mut result, certificates, target);
+        CheckResult {
+            timings: Some(timings),
+            ..result
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{CheckHistory, CheckResult, StateChange};
+use crate::snapshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentKind {
+    Outage,    // Down / Error / AssertionFailed が続いた
+    SlaBreach, // 許容時間超過が続いた
+}
+
+// update() で障害の記録がどう変わったか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    Unchanged,
+    Added, // 続いている障害に失敗を積んだ（ファイルには次に開く・閉じるとき／終了時に書く）
+    OpenedOrClosed, // 障害を開いた・閉じた（すぐ保存する）
+}
+
+impl Update {
+    pub fn changed(self) -> bool {
+        self != Update::Unchanged
+    }
+}
+
+// 障害1件分（Down になってから復旧するまで）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: u64,
+    pub url: String,
+    // ターゲットの name（あれば url ではなくこちらでターゲットを指す）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub kind: IncidentKind,
+    pub started_at: DateTime<Utc>,       // 最初に失敗したチェックの日時
+    pub ended_at: Option<DateTime<Utc>>, // 復旧したチェックの日時（続いている間は None）
+    pub failed_checks: u32,
+    pub first_error: Option<String>,
+    pub worst_latency_ms: Option<u64>,
+}
+
+impl Incident {
+    pub fn is_open(&self) -> bool {
+        self.ended_at.is_none()
+    }
+
+    // ターゲットのキー（CheckHistory::key と同じ）
+    pub fn key(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    // 続いている場合は現在までの長さ
+    pub fn duration(&self) -> chrono::Duration {
+        self.ended_at.unwrap_or_else(Utc::now) - self.started_at
+    }
+
+    fn add(&mut self, result: &CheckResult) {
+        self.failed_checks += 1;
+        if self.first_error.is_none() {
+            self.first_error = result.reason();
+        }
+        if let Some(rt) = result.response_time {
+            let ms = rt.as_millis() as u64;
+            self.worst_latency_ms = Some(self.worst_latency_ms.map_or(ms, |w| w.max(ms)));
+        }
+    }
+}
+
+// incidents.json に保存する障害の一覧（古い順）
+#[derive(Debug, Default)]
+pub struct IncidentLog {
+    path: PathBuf,
+    pub incidents: Vec<Incident>,
+    last_id: u64, // 消した障害の id を使い回さないよう、これまでの最大を覚えておく
+}
+
+impl IncidentLog {
+    // daemon 用。ファイルがなければ空から始める。壊れていれば上書きされないよう脇に移してから空で始める
+    pub fn load(path: &Path) -> Self {
+        let incidents = match parse(path) {
+            Ok(incidents) => incidents,
+            Err(ReadError::Parse(e)) => {
+                eprintln!("[RESTORE] {} が読めません: {}", path.display(), e);
+                match snapshot::quarantine(path) {
+                    Ok(moved) => eprintln!("[RESTORE] {} に移しました", moved.display()),
+                    Err(e) => eprintln!("[RESTORE] {} を移せません: {}", path.display(), e),
+                }
+                Vec::new()
+            }
+            Err(ReadError::Io(e)) => {
+                eprintln!("[RESTORE] {} を開けません: {}", path.display(), e);
+                Vec::new()
+            }
+        };
+
+        Self {
+            path: path.to_path_buf(),
+            last_id: incidents.iter().map(|i| i.id).max().unwrap_or(0),
+            incidents,
+        }
+    }
+
+    // view 用。ファイルには触らず、ない・読めない場合は空を返す
+    pub fn read(path: &Path) -> Vec<Incident> {
+        parse(path).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        snapshot::write_atomic(&self.path, &serde_json::to_vec_pretty(&self.incidents)?)
+    }
+
+    pub fn open_incident(&self, key: &str) -> Option<&Incident> {
+        self.incidents
+            .iter()
+            .rev()
+            .find(|i| i.key() == key && i.is_open())
+    }
+
+    // 閉じてから days 日（0 は無期限）を過ぎた障害を消し、消した数を返す
+    pub fn prune(&mut self, days: u64, now: DateTime<Utc>) -> usize {
+        if days == 0 {
+            return 0;
+        }
+        let cutoff = now - chrono::Duration::days(days as i64);
+        let before = self.incidents.len();
+        self.incidents
+            .retain(|i| i.ended_at.is_none_or(|ended| ended >= cutoff));
+        before - self.incidents.len()
+    }
+
+    // key のターゲットの、続いている障害と at に閉じた障害（update / close_open で変わったもの）
+    pub fn changed<'a>(
+        &'a self,
+        key: &'a str,
+        at: Option<DateTime<Utc>>,
+    ) -> impl Iterator<Item = &'a Incident> + 'a {
+        self.incidents
+            .iter()
+            .filter(move |i| i.key() == key && (i.is_open() || i.ended_at == at))
+    }
+
+    // key のターゲットの続いている障害を at で閉じる（ターゲットが設定から消えたとき）。閉じたものがあれば true
+    pub fn close_open(&mut self, key: &str, at: DateTime<Utc>) -> bool {
+        let mut closed = false;
+        for incident in self
+            .incidents
+            .iter_mut()
+            .filter(|i| i.key() == key && i.is_open())
+        {
+            incident.ended_at = Some(at);
+            closed = true;
+        }
+        closed
+    }
+
+    // 最新の結果と状態の変化から障害を開く・積む・閉じる
+    pub fn update(&mut self, history: &CheckHistory, changes: &[StateChange]) -> Update {
+        let Some(latest) = history.results.back() else {
+            return Update::Unchanged;
+        };
+        let mut update = Update::Unchanged;
+
+        if let Some(open) = self
+            .incidents
+            .iter_mut()
+            .rev()
+            .find(|i| i.key() == history.key() && i.is_open())
+        {
+            let closes = match open.kind {
+                // Down の間は許容時間の判定をしないので、SLA 側は Down になった時点で閉じる
+                IncidentKind::SlaBreach => changes
+                    .iter()
+                    .any(|c| matches!(c, StateChange::SlaRecovered | StateChange::Down)),
+                IncidentKind::Outage => changes.contains(&StateChange::Recovered),
+            };
+            if closes {
+                open.ended_at = Some(latest.timestamp);
+                update = Update::OpenedOrClosed;
+            } else if latest.is_failing() || latest.is_breached() {
+                open.add(latest);
+                update = Update::Added;
+            }
+        }
+
+        for change in changes {
+            let (kind, streak) = match change {
+                StateChange::Down => (IncidentKind::Outage, history.state.consecutive_failures),
+                StateChange::SlaBreach => {
+                    (IncidentKind::SlaBreach, history.state.consecutive_breaches)
+                }
+                _ => continue,
+            };
+            self.open(history, kind, streak as usize);
+            update = Update::OpenedOrClosed;
+        }
+        update
+    }
+
+    // fail_after 回分さかのぼって、最初に失敗したチェックから障害を始める
+    fn open(&mut self, history: &CheckHistory, kind: IncidentKind, streak: usize) {
+        let skip = history.results.len().saturating_sub(streak.max(1));
+        let mut failed = history.results.iter().skip(skip);
+        let Some(first) = failed.next() else {
+            return;
+        };
+
+        self.last_id += 1;
+        let mut incident = Incident {
+            id: self.last_id,
+            url: history.url.clone(),
+            name: history.name.clone(),
+            kind,
+            started_at: first.timestamp,
+            ended_at: None,
+            failed_checks: 0,
+            first_error: None,
+            worst_latency_ms: None,
+        };
+        incident.add(first);
+        for result in failed {
+            incident.add(result);
+        }
+        self.incidents.push(incident);
+    }
+}
+
+enum ReadError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+// ファイルがなければ空
+fn parse(path: &Path) -> Result<Vec<Incident>, ReadError> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(ReadError::Parse),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(ReadError::Io(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incident(id: u64, ended_at: Option<&str>) -> Incident {
+        Incident {
+            id,
+            url: "https://example.com".to_string(),
+            name: None,
+            kind: IncidentKind::Outage,
+            started_at: "2025-01-01T00:00:00Z".parse().unwrap(),
+            ended_at: ended_at.map(|t| t.parse().unwrap()),
+            failed_checks: 1,
+            first_error: None,
+            worst_latency_ms: None,
+        }
+    }
+
+    #[test]
+    fn read_leaves_a_corrupt_file_and_load_moves_it_aside() {
+        let dir = std::env::temp_dir().join(format!("ruliadema-incidents-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("incidents.json");
+        fs::write(&path, "{not json").unwrap();
+
+        assert!(IncidentLog::read(&path).is_empty());
+        assert!(path.exists());
+
+        assert!(IncidentLog::load(&path).incidents.is_empty());
+        assert!(!path.exists());
+        let moved: Vec<_> = fs::read_dir(&dir).unwrap().flatten().collect();
+        assert_eq!(moved.len(), 1);
+        assert!(
+            moved[0]
+                .file_name()
+                .to_string_lossy()
+                .starts_with("incidents.json.corrupt-")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_removes_closed_incidents_past_retention() {
+        let mut log = IncidentLog {
+            incidents: vec![
+                incident(1, Some("2025-01-02T00:00:00Z")),
+                incident(2, Some("2025-03-01T00:00:00Z")),
+                incident(3, None),
+            ],
+            last_id: 3,
+            ..IncidentLog::default()
+        };
+        let now = "2025-03-10T00:00:00Z".parse().unwrap();
+
+        assert_eq!(log.prune(0, now), 0);
+        assert_eq!(log.prune(30, now), 1);
+        let ids: Vec<u64> = log.incidents.iter().map(|i| i.id).collect();
+        assert_eq!(ids, [2, 3]);
+    }
+}
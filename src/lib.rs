@@ -1,6 +1,18 @@
 mod config;
-pub use config::Config;
+pub use config::{Config, RotationConfig, StorageBackend, StorageConfig, Target};
 
+pub mod alert;
 pub mod checker;
+pub mod incident;
+pub mod metrics;
 pub mod model;
-pub mod output;
\ No newline at end of file
+pub mod net;
+pub mod output;
+pub mod paths;
+pub mod remote;
+pub mod report;
+pub mod rotation;
+pub mod scheduler;
+pub mod server;
+pub mod snapshot;
+pub mod store;
@@ -1,126 +1,899 @@
-use ruliadema::Config;
-use ruliadema::checker::HttpChecker;
-use ruliadema::model::CheckHistory;
+use ruliadema::alert::Alerter;
+use ruliadema::checker::Checkers;
+use ruliadema::incident::{IncidentLog, Update};
+use ruliadema::metrics::Metrics;
+use ruliadema::model::{CheckHistory, CheckResult, CheckStatus, MAX_HISTORY, StateChange};
 use ruliadema::output::print_log;
+use ruliadema::paths::DataDir;
+use ruliadema::report::{self, TargetReport, format_percent};
+use ruliadema::scheduler::{Report, Scheduler};
+use ruliadema::server::{self, Shared, SharedState};
+use ruliadema::snapshot;
+use ruliadema::store::{self, Store, TimeRange};
+use ruliadema::{Config, Target};
 
+use clap::{Parser, Subcommand};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Semaphore;
-use tokio::time::{Duration, interval};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::{Semaphore, broadcast, mpsc};
+use tokio::time::{Duration, MissedTickBehavior, interval};
 
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::fs;
+
+// status.json を書き出す間隔（終了時には必ず書く）
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Parser)]
+#[command(version, about = "HTTP monitoring daemon")]
+struct Cli {
+    /// Config file (.toml / .yaml)
+    #[arg(
+        short,
+        long,
+        env = "RULIADEMA_CONFIG",
+        default_value = "config.toml",
+        global = true
+    )]
+    config: String,
+
+    /// Directory for status.json, ruliadema.log, breaches.json and incidents.json
+    #[arg(
+        short,
+        long,
+        env = "RULIADEMA_DATA_DIR",
+        default_value = ".",
+        global = true
+    )]
+    data_dir: PathBuf,
+
+    /// Log filter such as "info" or "ruliadema=debug" (overrides RUST_LOG)
+    #[arg(long, env = "RULIADEMA_LOG_LEVEL", global = true)]
+    log_level: Option<String>,
+
+    /// Check every target once, print the results and exit (status 1 if any check failed)
+    #[arg(long)]
+    once: bool,
+
+    /// With --once, check only targets carrying this tag (repeatable)
+    #[arg(long, requires = "once")]
+    tag: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Validate a config file and exit (status 1 if it has problems)
+    CheckConfig {
+        /// Defaults to --config
+        path: Option<String>,
+    },
+    /// Print uptime, SLA compliance and error budget per target from the stored results
+    Report {
+        /// Only this target (its name, or URL when unnamed)
+        #[arg(long)]
+        target: Option<String>,
+        /// Only targets carrying this tag (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Windows ending now, e.g. 90m, 24h, 7d, 2w (repeatable, default 1h 24h 7d 30d)
+        #[arg(short, long)]
+        window: Vec<String>,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    env_logger::init();
+    let cli = Cli::parse();
+
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(filter) = &cli.log_level {
+        logger.parse_filters(filter);
+    }
+    logger.init();
+
+    if let Some(Command::CheckConfig { path }) = &cli.command {
+        check_config(path.as_deref().unwrap_or(&cli.config));
+    }
 
     // config 読み込み
-    let config = Config::from_file("config.toml")?;
+    let mut config = Config::from_file(&cli.config)?;
+    let mut config_modified = modified(&cli.config);
 
-    // checker 初期化
-    let checker = Arc::new(HttpChecker::new(config.timeout_seconds)?);
+    if let Some(Command::Report {
+        target,
+        tag,
+        window,
+        json,
+    }) = &cli.command
+    {
+        let data_dir = DataDir::new(&cli.data_dir);
+        return report(&config, &data_dir, target.as_deref(), tag, window, *json);
+    }
 
-    // 状態の復元
-    let mut histories: HashMap<String, CheckHistory> = if let Ok(file) = File::open("status.json") {
-        serde_json::from_reader(file).unwrap_or_default()
-    } else {
-        HashMap::new()
-    };
+    if cli.once {
+        let ok = run_once(&config, &cli.tag).await?;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    let data_dir = DataDir::new(&cli.data_dir);
+    data_dir.create()?;
+
+    // チェック結果と違反記録の保存先（[storage]）
+    let mut store = store::open(&config.storage, &data_dir)?;
+
+    // 状態の復元。status.json が読めなければ退避し、保存先の直近の結果から組み立て直す
+    let (mut histories, rebuild): (HashMap<String, CheckHistory>, bool) =
+        match snapshot::load(&data_dir.status()) {
+            Ok(histories) => (histories, false),
+            Err(e) => {
+                eprintln!("[RESTORE] 前回の状態を読めません: {:#}", e);
+                match snapshot::quarantine(&data_dir.status()) {
+                    Ok(moved) => eprintln!(
+                        "[RESTORE] 読めなかったファイルを {} に移しました",
+                        moved.display()
+                    ),
+                    Err(e) => eprintln!("[RESTORE] 読めなかったファイルを移せません: {}", e),
+                }
+                (HashMap::new(), true)
+            }
+        };
 
+    // 以前の status.json にはヘッダなどの値がそのまま入っている（retired のターゲットは設定から上書きされない）
+    for history in histories.values_mut() {
+        history.request = std::mem::take(&mut history.request).redacted();
+    }
     for target in &config.targets {
         let history = histories
-            .entry(target.url.clone())
+            .entry(target.key().to_string())
             .or_insert_with(|| CheckHistory::new(target.url.clone(), target.acceptable_latency_ms));
-        history.acceptable_latency_ms = target.acceptable_latency_ms;
+        sync_history(history, target);
+        if rebuild {
+            rebuild_history(store.as_ref(), history);
+        }
+        seed_latencies(store.as_ref(), history);
     }
 
-    let semaphore = Arc::new(Semaphore::new(config.max_concurrency));
-    let mut ticker = interval(Duration::from_secs(config.interval_seconds));
+    // 障害の記録（incidents.json）
+    let mut incidents = IncidentLog::load(&data_dir.incidents());
+
+    // 設定から消えたターゲットはチェックせず、retired として履歴だけ残す
+    let now = chrono::Utc::now();
+    for history in histories.values_mut() {
+        if !history.is_retired() && !config.targets.iter().any(|t| t.key() == history.key()) {
+            retire(history, &mut incidents, now);
+            println!(
+                "[RESTORE] {} is no longer in the config, retired",
+                history.key()
+            );
+        }
+    }
+    prune_retired(&mut histories, config.retired_retention_days, now);
+
+    // 状態が変わったときの通知先
+    let mut alerter = Alerter::new(&config)?;
+
+    // 組み込み HTTP サーバ（[http] があるときだけ）から参照する状態
+    let buckets = config
+        .http
+        .as_ref()
+        .map(|http| http.latency_buckets_ms.clone())
+        .unwrap_or_default();
+    let shared: SharedState = Arc::new(RwLock::new(Shared {
+        started_at: chrono::Utc::now(),
+        config_path: cli.config.clone(),
+        histories: histories.clone(),
+        incidents: incidents.incidents.clone(),
+        metrics: Metrics::new(&buckets),
+        events: broadcast::channel(256).0,
+    }));
+    if let Some(http) = &config.http {
+        let addr = server::spawn(
+            http,
+            shared.clone(),
+            store::open(&config.storage, &data_dir)?,
+        )?;
+        println!("[HTTP] listening on http://{}", addr);
+    }
+
+    // ターゲットごとに自分の周期で監視させ、結果はチャネルで受け取る
+    let (tx, mut rx) = mpsc::channel::<Report>(64);
+    let mut scheduler = Scheduler::new(&config, tx)?;
+
+    for target in &config.targets {
+        scheduler.start(target.clone());
+    }
+
+    // 設定ファイルの更新は SIGHUP か更新日時の変化で検知する
+    let mut hangup = signal(SignalKind::hangup())?;
+    // SIGINT / SIGTERM で実行中のチェックを待ってから終わる
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut watch = interval(Duration::from_secs(2));
 
+    // 保存期間を過ぎた結果の間引き・削除（起動直後と1時間ごと）
+    let mut compaction = interval(Duration::from_secs(3600));
+
+    // status.json は結果のたびではなく、変化があったときに SNAPSHOT_INTERVAL ごとにまとめて書く
+    let mut snapshot_timer = interval(SNAPSHOT_INTERVAL);
+    snapshot_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut snapshot_dirty = false;
+
+    let signal_name = loop {
+        tokio::select! {
+            Some((key, result)) = rx.recv() => {
+                handle_report(store.as_mut(), &mut histories, &mut incidents, &alerter, &shared, &key, result);
+                snapshot_dirty = true;
+            }
+            _ = snapshot_timer.tick(), if snapshot_dirty => {
+                // 最新状態のスナップショット保存
+                if let Err(e) = snapshot::save(&data_dir.status(), &histories) {
+                    eprintln!("JSONの保存に失敗しました: {:#}", e);
+                }
+                snapshot_dirty = false;
+            }
+            _ = interrupt.recv() => break "SIGINT",
+            _ = terminate.recv() => break "SIGTERM",
+            _ = compaction.tick() => {
+                if prune_retired(&mut histories, config.retired_retention_days, chrono::Utc::now()) {
+                    publish_targets(&shared, &histories, &incidents);
+                    snapshot_dirty = true;
+                }
+                // 閉じてから breach_retention_days を過ぎた障害も消す
+                let pruned = incidents.prune(config.storage.breach_retention_days, chrono::Utc::now());
+                if pruned > 0 {
+                    println!("[STORE] removed {} closed incidents", pruned);
+                    if let Err(e) = incidents.save() {
+                        eprintln!("incidents.json の保存に失敗しました: {}", e);
+                    }
+                    if let Ok(mut shared) = shared.write() {
+                        shared.incidents = incidents.incidents.clone();
+                    }
+                }
+                match store.compact(&config.storage.retention(), chrono::Utc::now()) {
+                    Ok(c) if !c.is_empty() => println!(
+                        "[STORE] rolled up {} results, removed {} rollups, {} breaches and {} log segments",
+                        c.rolled_up, c.rollups_removed, c.breaches_removed, c.segments_removed
+                    ),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("[STORE] 古い記録の整理に失敗しました: {:#}", e),
+                }
+            }
+            _ = hangup.recv() => {
+                config_modified = modified(&cli.config);
+                reload(&cli.config, &mut config, &mut histories, &mut incidents, &mut scheduler);
+                alerter.configure(&config);
+                publish_targets(&shared, &histories, &incidents);
+                snapshot_dirty = true;
+            }
+            _ = watch.tick() => {
+                let current = modified(&cli.config);
+                if current != config_modified {
+                    config_modified = current;
+                    reload(&cli.config, &mut config, &mut histories, &mut incidents, &mut scheduler);
+                    alerter.configure(&config);
+                    publish_targets(&shared, &histories, &incidents);
+                    snapshot_dirty = true;
+                }
+            }
+        }
+    };
+
+    // 新しいチェックは始めず、実行中のチェックの結果を受け取り切ってから終わる（最大 shutdown_timeout_seconds）
+    let started = std::time::Instant::now();
+    println!(
+        "[SHUTDOWN] {} received, waiting up to {}s for running checks",
+        signal_name, config.shutdown_timeout_seconds
+    );
+    let tasks = scheduler.shutdown();
+    drop(scheduler);
+
+    let deadline =
+        tokio::time::Instant::now() + Duration::from_secs(config.shutdown_timeout_seconds);
+    let mut drained = 0;
+    let mut aborted = 0;
     loop {
-        ticker.tick().await;
-        let mut handles = Vec::new();
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some((key, result))) => {
+                handle_report(
+                    store.as_mut(),
+                    &mut histories,
+                    &mut incidents,
+                    &alerter,
+                    &shared,
+                    &key,
+                    result,
+                );
+                drained += 1;
+            }
+            // 全タスクが送信側を手放した
+            Ok(None) => break,
+            // 待ちきれなかったチェックは捨てる
+            Err(_) => {
+                for task in tasks.iter().filter(|task| !task.is_finished()) {
+                    task.abort();
+                    aborted += 1;
+                }
+                break;
+            }
+        }
+    }
+
+    // 終了直前の状態変化の通知も送り切る
+    if tokio::time::timeout_at(deadline, alerter.flush())
+        .await
+        .is_err()
+    {
+        eprintln!("[SHUTDOWN] 送信中の通知を待ちきれませんでした");
+    }
+
+    if let Err(e) = store.flush() {
+        eprintln!("[SHUTDOWN] 記録の書き出しに失敗しました: {:#}", e);
+    }
+    if let Err(e) = snapshot::save(&data_dir.status(), &histories) {
+        eprintln!("[SHUTDOWN] JSONの保存に失敗しました: {:#}", e);
+    }
+    // 続いている障害に積んだ失敗はまだ書いていない
+    if let Err(e) = incidents.save() {
+        eprintln!("[SHUTDOWN] incidents.json の保存に失敗しました: {}", e);
+    }
+    println!(
+        "[SHUTDOWN] recorded {} in-flight checks, aborted {}, saved {} targets in {:.1}s",
+        drained,
+        aborted,
+        histories.len(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
 
-        for history in histories.values_mut() {
-            let permit = semaphore.clone().acquire_owned().await?;
-            let checker = checker.clone();
-            let url = history.url.clone();
+// 1件のチェック結果を記録し、通知・障害・HTTP サーバ側の状態に反映する
+fn handle_report(
+    store: &mut dyn Store,
+    histories: &mut HashMap<String, CheckHistory>,
+    incidents: &mut IncidentLog,
+    alerter: &Alerter,
+    shared: &SharedState,
+    key: &str,
+    result: CheckResult,
+) {
+    let (changes, breach) = record(store, histories, key, result);
+    let Some(history) = histories.get(key) else {
+        return;
+    };
+    alerter.notify(history, &changes);
+    let update = incidents.update(history, &changes);
+    if update == Update::OpenedOrClosed
+        && let Err(e) = incidents.save()
+    {
+        eprintln!("incidents.json の保存に失敗しました: {}", e);
+    }
 
-            handles.push(tokio::spawn(async move {
-                let result = checker.check_once(&url).await;
-                drop(permit);
-                (url, result)
-            }));
+    if let Ok(mut shared) = shared.write() {
+        shared.metrics.observe(history);
+        shared.histories.insert(key.to_string(), history.clone());
+        shared.publish("history", history);
+        if let Some(breach) = &breach {
+            shared.publish("breach", breach);
+        }
+        if update.changed() {
+            // 開いた・積んだ・閉じた障害（閉じたものは終了日時が最新のチェック）だけを差し替える
+            let latest = history.results.back().map(|r| r.timestamp);
+            for incident in incidents.changed(key, latest) {
+                shared.upsert_incident(incident);
+                shared.publish("incident", incident);
+            }
         }
+    }
+}
+
+// 設定の読み直しで増減・変更したターゲット（retired で閉じた障害も）を HTTP サーバ側にも反映する
+fn publish_targets(
+    shared: &SharedState,
+    histories: &HashMap<String, CheckHistory>,
+    incidents: &IncidentLog,
+) {
+    if let Ok(mut shared) = shared.write() {
+        shared.histories = histories.clone();
+        shared.incidents = incidents.incidents.clone();
+        shared
+            .metrics
+            .retain(|key| histories.get(key).is_some_and(|h| !h.is_retired()));
+        shared.publish("status", histories);
+    }
+}
+
+// --once: 全ターゲット（tags を指定したらそのどれかを持つもの）を1回ずつ調べて表示する（ファイルには書き込まない）。失敗がなければ true
+async fn run_once(config: &Config, tags: &[String]) -> anyhow::Result<bool> {
+    let checkers = Arc::new(Checkers::new(
+        config.timeout_seconds,
+        config.retry_policy(),
+    )?);
+    let semaphore = Arc::new(Semaphore::new(config.max_concurrency));
 
-        // 結果を反映
-        for handle in handles {
-            let (url, result) = handle.await?;
+    let handles: Vec<_> = config
+        .targets
+        .iter()
+        .filter(|t| tags.is_empty() || t.tags.iter().any(|tag| tags.contains(tag)))
+        .cloned()
+        .map(|target| {
+            let checkers = checkers.clone();
+            let semaphore = semaphore.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let result = checkers.check_once(&target).await;
+                (target.key().to_string(), result)
+            })
+        })
+        .collect();
 
-            if let Some(history) = histories.get_mut(&url) {
-                // 判定用に許容時間を取得しておく
-                let acceptable_ms = history.acceptable_latency_ms;
+    let mut ok = true;
+    for handle in handles {
+        let (key, result) = handle.await?;
+        print_log(&key, &result);
+        // Warning（証明書の期限切れ間近）は失敗として扱わない
+        ok &= matches!(result.status, CheckStatus::Up | CheckStatus::Warning);
+    }
+    Ok(ok)
+}
+
+// report: 保存先の結果から期間ごとの稼働率・SLA 達成率・error budget の残りを集計して表示する
+fn report(
+    config: &Config,
+    data_dir: &DataDir,
+    target: Option<&str>,
+    tags: &[String],
+    windows: &[String],
+    json: bool,
+) -> anyhow::Result<()> {
+    let windows: Vec<String> = if windows.is_empty() {
+        report::DEFAULT_WINDOWS.map(String::from).to_vec()
+    } else {
+        windows.to_vec()
+    };
+    for window in &windows {
+        report::parse_window(window)?;
+    }
 
+    let targets: Vec<&Target> = config
+        .targets
+        .iter()
+        .filter(|t| target.is_none_or(|key| t.key() == key))
+        .filter(|t| tags.is_empty() || t.tags.iter().any(|tag| tags.contains(tag)))
+        .collect();
+    if let Some(key) = target
+        && targets.is_empty()
+    {
+        anyhow::bail!("unknown target {}", key);
+    }
+
+    let store = store::open(&config.storage, data_dir)?;
+    let mut reports = Vec::new();
+    for target in targets {
+        let mut history = CheckHistory::new(target.url.clone(), target.acceptable_latency_ms);
+        sync_history(&mut history, target);
+        reports.push(TargetReport::new(store.as_ref(), &history, &windows)?);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+    for (i, target) in reports.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let slo = target.windows.first().map_or(0.0, |w| w.slo_percent);
+        if target.target == target.url {
+            println!("{} (SLO {}%)", target.target, slo);
+        } else {
+            println!("{} {} (SLO {}%)", target.target, target.url, slo);
+        }
+        println!(
+            "  {:<8} {:>8} {:>9} {:>9} {:>9} {:>12}",
+            "WINDOW", "CHECKS", "FAILURES", "UPTIME", "SLA", "BUDGET LEFT"
+        );
+        for w in &target.windows {
+            println!(
+                "  {:<8} {:>8} {:>9} {:>9} {:>9} {:>12}",
+                w.window,
+                w.checks,
+                w.failures,
+                format_percent(w.uptime_percent, 3),
+                format_percent(w.sla_percent, 3),
+                format_percent(w.error_budget_remaining_percent, 1)
+            );
+        }
+    }
+    Ok(())
+}
+
+// check-config: 問題があれば内容を表示して終了コード 1 で終わる
+fn check_config(path: &str) -> ! {
+    match Config::from_file(path) {
+        Ok(config) => {
+            println!("[OK] {}: {} targets", path, config.targets.len());
+            std::process::exit(0);
+        }
+        Err(e) => {
+            println!("[NG] {}", path);
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// 保存先に残っている直近の結果を積み直す（状態・連続失敗数なども結果から計算し直される）
+fn rebuild_history(store: &dyn Store, history: &mut CheckHistory) {
+    let range = TimeRange {
+        limit: Some(MAX_HISTORY),
+        ..TimeRange::default()
+    };
+    match store.results(history.key(), &range) {
+        Ok(results) => {
+            let count = results.len();
+            for result in results {
                 history.push(result);
+            }
+            println!("[RESTORE] {} restored {} results", history.key(), count);
+        }
+        Err(e) => eprintln!("[RESTORE] {} の結果を読めません: {:#}", history.key(), e),
+    }
+}
 
-                if let Some(latest_result) = history.results.back() {
-                    // コンソールへの出力
-                    print_log(&url, latest_result);
-
-                    let rt_ms = latest_result
-                        .response_time
-                        .map(|d| d.as_millis() as u64)
-                        .unwrap_or(0);
-
-                    // 永久保存用ログ (ruliadema.log) への追記
-                    if let Ok(mut log_file) = OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open("ruliadema.log")
-                    {
-                        let log_entry = serde_json::json!({
-                            "url": url,
-                            "response_time_ms": rt_ms,
-                            "result": latest_result
-                        });
-                        if let Ok(json_line) = serde_json::to_string(&log_entry) {
-                            let _ = writeln!(log_file, "{}", json_line);
-                        }
-                    }
+// 百分位の窓を直近の結果で埋め直す（latency_sla の窓が status.json の結果より長ければ保存先から読む）
+fn seed_latencies(store: &dyn Store, history: &mut CheckHistory) {
+    let range = match history.latency_sla {
+        Some(sla) if sla.minutes.is_some() || sla.checks.is_some_and(|c| c > MAX_HISTORY) => {
+            TimeRange {
+                from: sla
+                    .minutes
+                    .map(|m| chrono::Utc::now() - chrono::Duration::minutes(m as i64)),
+                to: None,
+                limit: sla.checks,
+            }
+        }
+        _ => {
+            let results = std::mem::take(&mut history.results);
+            history.seed_latencies(&results);
+            history.results = results;
+            return;
+        }
+    };
+    match store.results(history.key(), &range) {
+        Ok(results) => history.seed_latencies(&results),
+        Err(e) => eprintln!("[RESTORE] {} の結果を読めません: {:#}", history.key(), e),
+    }
+}
 
-                    // タイムアウト(取得失敗) または レスポンスタイムが許容時間を超えた場合
-                    let is_error = latest_result.response_time.is_none();
-                    if is_error || rt_ms > acceptable_ms {
-                        if let Ok(mut breach_file) = OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open("breaches.json")
-                        {
-                            let breach_entry = serde_json::json!({
-                                "url": url,
-                                "response_time_ms": rt_ms,
-                                "acceptable_latency_ms": acceptable_ms,
-                                "diff_ms": if is_error { 0 } else { rt_ms.saturating_sub(acceptable_ms) },
-                                "is_error": is_error,
-                                "result": latest_result // タイムスタンプやステータスコードを含めるため
-                            });
-
-                            if let Ok(json_line) = serde_json::to_string(&breach_entry) {
-                                let _ = writeln!(breach_file, "{}", json_line);
-                            }
-                        }
-                    }
-                    // ▲▲ ここまで ▲▲
-                }
+// 設定ファイルの内容を履歴側（status.json / TUI 表示用）に反映する
+fn sync_history(history: &mut CheckHistory, target: &Target) {
+    history.url = target.url.clone();
+    history.name = target.name.clone();
+    history.group = target.group.clone();
+    history.tags = target.tags.clone();
+    history.acceptable_latency_ms = target.acceptable_latency_ms;
+    history.kind = target.kind;
+    history.latency_phase = target.latency_phase;
+    history.latency_sla = target.latency_sla;
+    history.request = target.request_spec();
+    history.thresholds = target.thresholds();
+    history.slo_percent = target.slo_percent;
+    history.retired_at = None;
+}
+
+// 設定から消えたターゲットを retired にし、続いている障害を閉じる
+fn retire(
+    history: &mut CheckHistory,
+    incidents: &mut IncidentLog,
+    at: chrono::DateTime<chrono::Utc>,
+) {
+    history.retired_at = Some(at);
+    if incidents.close_open(history.key(), at)
+        && let Err(e) = incidents.save()
+    {
+        eprintln!("incidents.json の保存に失敗しました: {}", e);
+    }
+}
+
+// retired になってから retired_retention_days を過ぎた履歴を消す。消したものがあれば true
+fn prune_retired(
+    histories: &mut HashMap<String, CheckHistory>,
+    days: u64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    if days == 0 {
+        return false;
+    }
+    let cutoff = now - chrono::Duration::days(days as i64);
+    let before = histories.len();
+    histories.retain(|key, history| {
+        let expired = history.retired_at.is_some_and(|at| at < cutoff);
+        if expired {
+            println!(
+                "[STORE] dropped {} (retired for more than {} days)",
+                key, days
+            );
+        }
+        !expired
+    });
+    histories.len() != before
+}
+
+// 設定ファイルを読み直し、ターゲットの追加・削除・変更を反映する。読めなければ今の設定を使い続ける
+fn reload(
+    path: &str,
+    config: &mut Config,
+    histories: &mut HashMap<String, CheckHistory>,
+    incidents: &mut IncidentLog,
+    scheduler: &mut Scheduler,
+) {
+    let next = match Config::from_file(path) {
+        Ok(next) => next,
+        Err(e) => {
+            eprintln!(
+                "[RELOAD] {} の読み込みに失敗したため、現在の設定を使い続けます: {}",
+                path, e
+            );
+            return;
+        }
+    };
+
+    let globals_changed = next.interval_seconds != config.interval_seconds
+        || next.timeout_seconds != config.timeout_seconds
+        || next.retry_policy() != config.retry_policy()
+        || next.max_concurrency != config.max_concurrency;
+    if globals_changed {
+        if let Err(e) = scheduler.configure(&next) {
+            eprintln!(
+                "[RELOAD] 設定を反映できなかったため、現在の設定を使い続けます: {}",
+                e
+            );
+            return;
+        }
+        println!(
+            "[RELOAD] interval={}s timeout={}s retries={} max_concurrency={}",
+            next.interval_seconds, next.timeout_seconds, next.retries, next.max_concurrency
+        );
+    }
+
+    // 待ち受けアドレスと保存先は起動時にしか反映しない（保存期間は次の整理から反映される）
+    if next.http != config.http {
+        println!("[RELOAD] [http] の変更は再起動後に反映されます");
+    }
+    if next.storage.backend != config.storage.backend {
+        println!("[RELOAD] [storage] backend の変更は再起動後に反映されます");
+    }
+    if next.storage.rotation != config.storage.rotation {
+        println!("[RELOAD] [storage.rotation] の変更は再起動後に反映されます");
+    }
+
+    let now = chrono::Utc::now();
+    for prev in &config.targets {
+        if !next.targets.iter().any(|t| t.key() == prev.key()) {
+            scheduler.stop(prev.key());
+            if let Some(history) = histories.get_mut(prev.key()) {
+                retire(history, incidents, now);
+            }
+            println!("[RELOAD] retired {}", prev.key());
+        }
+    }
+
+    for target in &next.targets {
+        let changes = match config.targets.iter().find(|t| t.key() == target.key()) {
+            None => {
+                println!("[RELOAD] added {}", target.key());
+                None
             }
+            Some(prev) if prev != target => Some(describe_changes(prev, target)),
+            Some(_) => continue,
+        };
+        if let Some(changes) = changes {
+            println!("[RELOAD] changed {} {}", target.key(), changes);
         }
 
-        // 最新状態のスナップショット保存
-        if let Ok(file) = File::create("status.json") {
-            if let Err(e) = serde_json::to_writer_pretty(file, &histories) {
-                eprintln!("JSONの保存に失敗しました: {}", e);
+        let history = histories
+            .entry(target.key().to_string())
+            .or_insert_with(|| CheckHistory::new(target.url.clone(), target.acceptable_latency_ms));
+        sync_history(history, target);
+        scheduler.start(target.clone());
+    }
+
+    *config = next;
+}
+
+// 変更のあった項目を "acceptable_latency_ms 500 -> 300, request" のように並べる
+fn describe_changes(prev: &Target, next: &Target) -> String {
+    let mut changes = Vec::new();
+    if prev.url != next.url {
+        changes.push(format!("url {} -> {}", prev.url, next.url));
+    }
+    if prev.group != next.group {
+        changes.push(format!("group {:?} -> {:?}", prev.group, next.group));
+    }
+    if prev.tags != next.tags {
+        changes.push(format!("tags {:?} -> {:?}", prev.tags, next.tags));
+    }
+    if prev.kind != next.kind {
+        changes.push(format!("type {:?} -> {:?}", prev.kind, next.kind));
+    }
+    if prev.acceptable_latency_ms != next.acceptable_latency_ms {
+        changes.push(format!(
+            "acceptable_latency_ms {} -> {}",
+            prev.acceptable_latency_ms, next.acceptable_latency_ms
+        ));
+    }
+    if prev.latency_sla != next.latency_sla {
+        changes.push(format!(
+            "latency_sla {} -> {}",
+            prev.latency_sla.map_or("-".to_string(), |s| s.label()),
+            next.latency_sla.map_or("-".to_string(), |s| s.label())
+        ));
+    }
+    if prev.latency_phase != next.latency_phase {
+        changes.push(format!(
+            "latency_phase {:?} -> {:?}",
+            prev.latency_phase, next.latency_phase
+        ));
+    }
+    if prev.interval_seconds != next.interval_seconds {
+        changes.push(format!(
+            "interval_seconds {:?} -> {:?}",
+            prev.interval_seconds, next.interval_seconds
+        ));
+    }
+    if prev.timeout_seconds != next.timeout_seconds {
+        changes.push(format!(
+            "timeout_seconds {:?} -> {:?}",
+            prev.timeout_seconds, next.timeout_seconds
+        ));
+    }
+    if prev.retries != next.retries {
+        changes.push(format!("retries {:?} -> {:?}", prev.retries, next.retries));
+    }
+    if prev.retry_backoff_ms != next.retry_backoff_ms {
+        changes.push(format!(
+            "retry_backoff_ms {:?} -> {:?}",
+            prev.retry_backoff_ms, next.retry_backoff_ms
+        ));
+    }
+    if prev.thresholds() != next.thresholds() {
+        changes.push(format!(
+            "thresholds {:?} -> {:?}",
+            prev.thresholds(),
+            next.thresholds()
+        ));
+    }
+    if prev.slo_percent != next.slo_percent {
+        changes.push(format!(
+            "slo_percent {} -> {}",
+            prev.slo_percent, next.slo_percent
+        ));
+    }
+    if prev.cert_warning_days != next.cert_warning_days {
+        changes.push(format!(
+            "cert_warning_days {} -> {}",
+            prev.cert_warning_days, next.cert_warning_days
+        ));
+    }
+    if prev.method != next.method
+        || prev.headers != next.headers
+        || prev.query != next.query
+        || prev.body != next.body
+        || prev.json != next.json
+    {
+        changes.push("request".to_string());
+    }
+    if prev.assertions != next.assertions {
+        changes.push("assert".to_string());
+    }
+    if prev.dns != next.dns {
+        changes.push("dns".to_string());
+    }
+    if prev.notify != next.notify {
+        changes.push(format!("notify {:?} -> {:?}", prev.notify, next.notify));
+    }
+    changes.join(", ")
+}
+
+// 1件の結果を履歴に積み、コンソール・ログ・違反ログへ書き出す。状態の変化と breaches.json に書いた記録を返す
+fn record(
+    store: &mut dyn Store,
+    histories: &mut HashMap<String, CheckHistory>,
+    key: &str,
+    result: CheckResult,
+) -> (Vec<StateChange>, Option<serde_json::Value>) {
+    let mut changes = Vec::new();
+    let mut breach = None;
+    if let Some(history) = histories.get_mut(key) {
+        // 判定用に許容時間と対象区間を取得しておく
+        let acceptable_ms = history.acceptable_latency_ms;
+        let phase = history.latency_phase;
+
+        changes = history.push(result);
+
+        if let Some(latest_result) = history.results.back() {
+            // コンソールへの出力
+            print_log(key, latest_result);
+
+            let rt_ms = latest_result
+                .response_time
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+
+            // 永久保存用の履歴（ruliadema.log / ruliadema.db）への追記
+            if let Err(e) = store.append_result(key, &history.url, latest_result) {
+                eprintln!("履歴の保存に失敗しました: {:#}", e);
+            }
+
+            // 許容時間と比較する区間の所要時間（既定はレスポンスタイム全体）
+            // latency_sla があれば1回ごとではなく窓の百分位（diff_from_acceptable は百分位との差）
+            let measured_ms = match history.latency_sla {
+                Some(_) => latest_result
+                    .diff_from_acceptable
+                    .map(|d| (acceptable_ms as i128 + d).max(0) as u64)
+                    .unwrap_or(0),
+                None => latest_result
+                    .latency(phase)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+            };
+
+            // 失敗（Down / Error / 検証失敗）または 所要時間が許容時間を超えた場合
+            // ただし fail_after 回続いて Down / 許容時間超過になっている間だけ記録する（単発の失敗は記録しない）
+            let is_error = latest_result.response_time.is_none();
+            // 証明書期限切れ間近は Warning になったときの1回だけ記録する（続いている間は毎回書かない）
+            let is_warning = matches!(latest_result.status, CheckStatus::Warning);
+            let entered_warning = is_warning
+                && history
+                    .results
+                    .iter()
+                    .rev()
+                    .nth(1)
+                    .is_none_or(|prev| !matches!(prev.status, CheckStatus::Warning));
+            let confirmed = history.state.down || history.state.sla_breached;
+            if entered_warning
+                || ((latest_result.is_failing() || measured_ms > acceptable_ms) && confirmed)
+            {
+                let mut breach_entry = serde_json::json!({
+                    "url": history.url,
+                    "response_time_ms": rt_ms,
+                    "acceptable_latency_ms": acceptable_ms,
+                    "latency_phase": phase,
+                    "diff_ms": if is_error { 0 } else { measured_ms.saturating_sub(acceptable_ms) },
+                    "is_error": is_error,
+                    "cert_days_left": latest_result.cert_days_left(),
+                    "result": latest_result // タイムスタンプやステータスコードを含めるため
+                });
+
+                // name のあるターゲットだけ付ける
+                if let Some(name) = &history.name {
+                    breach_entry["name"] = serde_json::Value::from(name.as_str());
+                }
+                // 百分位で判定したときは、その百分位と値も残す
+                if let Some(sla) = &history.latency_sla {
+                    breach_entry["percentile"] = serde_json::Value::from(sla.percentile);
+                    breach_entry["percentile_ms"] = serde_json::Value::from(measured_ms);
+                }
+
+                if let Err(e) = store.append_breach(key, &breach_entry) {
+                    eprintln!("違反記録の保存に失敗しました: {:#}", e);
+                }
+                breach = Some(breach_entry);
             }
+            // ▲▲ ここまで ▲▲
         }
     }
+    (changes, breach)
 }
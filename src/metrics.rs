@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::model::{CheckHistory, CheckKind, CheckStatus, LatencyPhase, MAX_HISTORY, Percentiles};
+
+// ターゲットごとの累積値（daemon の起動からの合計）と最新値
+#[derive(Debug, Clone, Default)]
+struct Series {
+    url: String,
+    group: Option<String>,
+    kind: CheckKind,
+    up: bool,
+    last_latency: Option<f64>,
+    phases: Vec<(&'static str, f64)>,
+    acceptable_latency: f64,
+    percentiles: Option<Percentiles>,
+    cert_expiry: Option<i64>,
+    checks: BTreeMap<String, u64>, // status ごとの回数
+    breaches: u64,
+    buckets: Vec<u64>, // buckets_ms と同じ並び（それ以下に収まった回数）
+    latency_sum: f64,
+    latency_count: u64,
+}
+
+// Prometheus 形式で出力するための集計
+#[derive(Debug, Default)]
+pub struct Metrics {
+    buckets_ms: Vec<u64>,
+    series: BTreeMap<String, Series>,
+}
+
+impl Metrics {
+    pub fn new(buckets_ms: &[u64]) -> Self {
+        let mut buckets_ms = buckets_ms.to_vec();
+        buckets_ms.sort_unstable();
+        buckets_ms.dedup();
+        Self {
+            buckets_ms,
+            series: BTreeMap::new(),
+        }
+    }
+
+    // 最新の結果を取り込む（CheckHistory::push の後に呼ぶ）
+    pub fn observe(&mut self, history: &CheckHistory) {
+        let Some(latest) = history.results.back() else {
+            return;
+        };
+        let bucket_count = self.buckets_ms.len();
+        let series = self
+            .series
+            .entry(history.key().to_string())
+            .or_insert_with(|| Series {
+                buckets: vec![0; bucket_count],
+                ..Series::default()
+            });
+
+        series.url = history.url.clone();
+        series.group = history.group.clone();
+        series.kind = history.kind;
+        series.up = !history.state.down;
+        series.acceptable_latency = history.acceptable_latency_ms as f64 / 1000.0;
+        series.percentiles = history.state.percentiles;
+        series.cert_expiry = latest.cert_expires_at.map(|at| at.timestamp());
+        series.last_latency = latest.response_time.map(|d| d.as_secs_f64());
+        series.phases = latest
+            .timings
+            .as_ref()
+            .map(|t| {
+                [
+                    ("dns", t.dns),
+                    ("connect", t.connect),
+                    ("tls", t.tls),
+                    ("ttfb", t.ttfb),
+                    ("download", t.download),
+                ]
+                .into_iter()
+                .filter_map(|(name, d)| d.map(|d| (name, d.as_secs_f64())))
+                .collect()
+            })
+            .unwrap_or_default();
+
+        *series
+            .checks
+            .entry(status_label(latest.status).to_string())
+            .or_default() += 1;
+        if latest.is_breached() {
+            series.breaches += 1;
+        }
+
+        if let Some(rt) = latest.latency(LatencyPhase::Total) {
+            let ms = rt.as_millis() as u64;
+            for (count, bound) in series.buckets.iter_mut().zip(&self.buckets_ms) {
+                if ms <= *bound {
+                    *count += 1;
+                }
+            }
+            series.latency_sum += rt.as_secs_f64();
+            series.latency_count += 1;
+        }
+    }
+
+    // 設定から外れたターゲットの系列を消す
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        self.series.retain(|key, _| keep(key));
+    }
+
+    // text exposition format (version 0.0.4)
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        // name はターゲットのキー（name がなければ URL と同じ）
+        let labels = |key: &str, s: &Series| {
+            format!(
+                "url=\"{}\",name=\"{}\",group=\"{}\",type=\"{}\"",
+                escape(&s.url),
+                escape(key),
+                escape(s.group.as_deref().unwrap_or_default()),
+                format!("{:?}", s.kind).to_lowercase()
+            )
+        };
+
+        header(
+            &mut out,
+            "ruliadema_up",
+            "gauge",
+            "1 if the target is up (after fail_after / recover_after), 0 if down",
+        );
+        for (key, s) in &self.series {
+            let _ = writeln!(out, "ruliadema_up{{{}}} {}", labels(key, s), s.up as u8);
+        }
+
+        header(
+            &mut out,
+            "ruliadema_last_latency_seconds",
+            "gauge",
+            "Total latency of the last check",
+        );
+        for (key, s) in &self.series {
+            if let Some(v) = s.last_latency {
+                let _ = writeln!(
+                    out,
+                    "ruliadema_last_latency_seconds{{{}}} {}",
+                    labels(key, s),
+                    v
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "ruliadema_last_phase_latency_seconds",
+            "gauge",
+            "Latency of each phase of the last check",
+        );
+        for (key, s) in &self.series {
+            for (phase, v) in &s.phases {
+                let _ = writeln!(
+                    out,
+                    "ruliadema_last_phase_latency_seconds{{{},phase=\"{}\"}} {}",
+                    labels(key, s),
+                    phase,
+                    v
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "ruliadema_acceptable_latency_seconds",
+            "gauge",
+            "Configured latency limit (acceptable_latency_ms)",
+        );
+        for (key, s) in &self.series {
+            let _ = writeln!(
+                out,
+                "ruliadema_acceptable_latency_seconds{{{}}} {}",
+                labels(key, s),
+                s.acceptable_latency
+            );
+        }
+
+        header(
+            &mut out,
+            "ruliadema_latency_percentile_seconds",
+            "gauge",
+            &format!(
+                "Latency percentiles of recent successful checks (latency_sla window, or the last {} checks)",
+                MAX_HISTORY
+            ),
+        );
+        for (key, s) in &self.series {
+            let Some(p) = &s.percentiles else {
+                continue;
+            };
+            for (quantile, ms) in [
+                ("0.5", p.p50),
+                ("0.9", p.p90),
+                ("0.95", p.p95),
+                ("0.99", p.p99),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "ruliadema_latency_percentile_seconds{{{},quantile=\"{}\"}} {}",
+                    labels(key, s),
+                    quantile,
+                    ms as f64 / 1000.0
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "ruliadema_latency_seconds",
+            "histogram",
+            "Total latency of responses",
+        );
+        for (key, s) in &self.series {
+            let l = labels(key, s);
+            for (count, bound) in s.buckets.iter().zip(&self.buckets_ms) {
+                let _ = writeln!(
+                    out,
+                    "ruliadema_latency_seconds_bucket{{{},le=\"{}\"}} {}",
+                    l,
+                    *bound as f64 / 1000.0,
+                    count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "ruliadema_latency_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                l, s.latency_count
+            );
+            let _ = writeln!(
+                out,
+                "ruliadema_latency_seconds_sum{{{}}} {}",
+                l, s.latency_sum
+            );
+            let _ = writeln!(
+                out,
+                "ruliadema_latency_seconds_count{{{}}} {}",
+                l, s.latency_count
+            );
+        }
+
+        header(
+            &mut out,
+            "ruliadema_checks_total",
+            "counter",
+            "Checks performed, by result status",
+        );
+        for (key, s) in &self.series {
+            for (status, count) in &s.checks {
+                let _ = writeln!(
+                    out,
+                    "ruliadema_checks_total{{{},status=\"{}\"}} {}",
+                    labels(key, s),
+                    status,
+                    count
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "ruliadema_sla_breaches_total",
+            "counter",
+            "Checks slower than the latency limit",
+        );
+        for (key, s) in &self.series {
+            let _ = writeln!(
+                out,
+                "ruliadema_sla_breaches_total{{{}}} {}",
+                labels(key, s),
+                s.breaches
+            );
+        }
+
+        header(
+            &mut out,
+            "ruliadema_cert_expiry_timestamp_seconds",
+            "gauge",
+            "Earliest certificate expiry in the chain (unix time)",
+        );
+        for (key, s) in &self.series {
+            if let Some(v) = s.cert_expiry {
+                let _ = writeln!(
+                    out,
+                    "ruliadema_cert_expiry_timestamp_seconds{{{}}} {}",
+                    labels(key, s),
+                    v
+                );
+            }
+        }
+
+        out
+    }
+}
+
+fn status_label(status: CheckStatus) -> &'static str {
+    match status {
+        CheckStatus::Up => "up",
+        CheckStatus::Down => "down",
+        CheckStatus::Error => "error",
+        CheckStatus::AssertionFailed => "assertion_failed",
+        CheckStatus::Warning => "warning",
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// ラベル値の \ " 改行をエスケープする
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
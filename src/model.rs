@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -8,6 +8,65 @@ pub enum CheckStatus {
     Up,
     Down,
     Error,
+    AssertionFailed, // 応答はあったが [targets.assert] の検証に失敗した
+    Warning,         // 応答は正常だが証明書の期限が近い
+}
+
+// サーバ証明書の概要（チェーンの先頭がサーバ自身の証明書）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub not_after: DateTime<Utc>,
+}
+
+// 取得失敗（CheckStatus::Error）の分類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+    Dns,
+    ConnectRefused,
+    Connect,
+    Tls,
+    Timeout,
+    RedirectLoop,
+    BodyRead,
+    Request, // リクエストの組み立てに失敗（不正なメソッドやヘッダなど）
+    Other,
+}
+
+// 1回のチェックにかかった時間の内訳（リダイレクトがあれば合算）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    pub ttfb: Option<Duration>, // リクエスト送信からレスポンスヘッダ受信まで
+    pub download: Option<Duration>, // ボディの受信
+}
+
+// チェックの種類（config の type）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckKind {
+    #[default]
+    Http,
+    Tcp, // ポートへの TCP 接続のみ（ICMP を使わない ping）
+    Dns,
+    Tls,
+}
+
+// 許容時間と比較する区間
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LatencyPhase {
+    #[default]
+    Total,
+    Dns,
+    Connect,
+    Tls,
+    Ttfb,
+    Download,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,16 +77,380 @@ pub struct CheckResult {
     pub diff_from_prev: Option<i128>,
     #[serde(default)] // ← 古いstatus.json対策
     pub diff_from_acceptable: Option<i128>, // 許容時間との差分
+    #[serde(default)]
+    pub failure_reason: Option<String>, // 検証失敗の理由
+    #[serde(default)]
+    pub status_code: Option<u16>, // HTTP ステータスコード
+    #[serde(default)]
+    pub error_kind: Option<ErrorKind>,
+    #[serde(default)]
+    pub error_message: Option<String>,
+    #[serde(default)]
+    pub timings: Option<PhaseTimings>,
+    #[serde(default)]
+    pub cert_expires_at: Option<DateTime<Utc>>, // チェーン中で最も早い有効期限
+    #[serde(default)]
+    pub attempts: u32, // 再試行を含めた試行回数
+    // 受け取った証明書チェーン（CheckHistory に移すので結果ごとには保存しない）
+    #[serde(skip)]
+    pub certificates: Vec<CertificateInfo>,
+}
+
+impl CheckResult {
+    pub fn new(status: CheckStatus) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            status,
+            response_time: None,
+            diff_from_prev: None,
+            diff_from_acceptable: None, // 初期値はNoneにしておく
+            failure_reason: None,
+            status_code: None,
+            error_kind: None,
+            error_message: None,
+            timings: None,
+            cert_expires_at: None,
+            attempts: 1,
+            certificates: Vec::new(),
+        }
+    }
+
+    pub fn error(kind: ErrorKind, message: String) -> Self {
+        Self {
+            error_kind: Some(kind),
+            error_message: Some(message),
+            ..Self::new(CheckStatus::Error)
+        }
+    }
+
+    pub fn latency(&self, phase: LatencyPhase) -> Option<Duration> {
+        let timings = self.timings.as_ref();
+        match phase {
+            LatencyPhase::Total => self.response_time,
+            LatencyPhase::Dns => timings.and_then(|t| t.dns),
+            LatencyPhase::Connect => timings.and_then(|t| t.connect),
+            LatencyPhase::Tls => timings.and_then(|t| t.tls),
+            LatencyPhase::Ttfb => timings.and_then(|t| t.ttfb),
+            LatencyPhase::Download => timings.and_then(|t| t.download),
+        }
+    }
+
+    // Down / Error / AssertionFailed（Warning は失敗に含めない）
+    pub fn is_failing(&self) -> bool {
+        !matches!(self.status, CheckStatus::Up | CheckStatus::Warning)
+    }
+
+    // 成功したが許容時間を超えた
+    pub fn is_breached(&self) -> bool {
+        !self.is_failing() && self.diff_from_acceptable.is_some_and(|d| d > 0)
+    }
+
+    // 失敗の理由（検証失敗の理由、なければ "Kind: message"）
+    pub fn reason(&self) -> Option<String> {
+        if let Some(reason) = &self.failure_reason {
+            return Some(reason.clone());
+        }
+        match (&self.error_kind, &self.error_message) {
+            (Some(kind), Some(message)) => Some(format!("{:?}: {}", kind, message)),
+            (Some(kind), None) => Some(format!("{:?}", kind)),
+            _ => None,
+        }
+    }
+
+    // 証明書の残り日数（期限切れならマイナス）
+    pub fn cert_days_left(&self) -> Option<i64> {
+        self.cert_expires_at
+            .map(|at| (at - self.timestamp).num_days())
+    }
+}
+
+// ターゲットに送るリクエストの定義（TUI の Detail 表示用）。トークンなどを含むので値は伏せて持つ
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestSpec {
+    pub method: String,
+    pub headers: BTreeMap<String, String>,
+    pub query: BTreeMap<String, String>,
+    pub body: Option<String>,
+}
+
+// 伏せた値の代わりに入れる文字列
+pub const REDACTED: &str = "********";
+
+impl RequestSpec {
+    // ヘッダ・クエリの値とボディを伏せたもの（名前とメソッドは残す）
+    pub fn redacted(self) -> Self {
+        let redact = |map: BTreeMap<String, String>| {
+            map.into_keys()
+                .map(|key| (key, REDACTED.to_string()))
+                .collect()
+        };
+        Self {
+            method: self.method,
+            headers: redact(self.headers),
+            query: redact(self.query),
+            body: self.body.map(|_| REDACTED.to_string()),
+        }
+    }
+}
+
+pub const MAX_HISTORY: usize = 50;
+
+// 百分位の窓に残すサンプルの上限（minutes の窓でもこれ以上は持たない）
+const MAX_LATENCY_SAMPLES: usize = 10_000;
+
+// 許容時間を1回ごとではなく、直近 checks 回または minutes 分の百分位に対して判定する（[targets.latency_sla]）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LatencySla {
+    pub percentile: f64, // 例: 95 なら p95 が acceptable_latency_ms 以下であること
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checks: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minutes: Option<u64>,
+}
+
+impl LatencySla {
+    // "p95 of last 20 checks" / "p99 of last 10 min"
+    pub fn label(&self) -> String {
+        match (self.checks, self.minutes) {
+            (Some(checks), _) => format!("p{} of last {} checks", self.percentile, checks),
+            (None, Some(minutes)) => format!("p{} of last {} min", self.percentile, minutes),
+            (None, None) => format!("p{}", self.percentile),
+        }
+    }
+}
+
+// 直近の成功したチェックの所要時間の百分位（ミリ秒）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub samples: usize,
+    pub p50: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+impl Percentiles {
+    // サンプルがなければ None
+    pub fn of(samples: impl IntoIterator<Item = u64>) -> Option<Self> {
+        let mut sorted: Vec<u64> = samples.into_iter().collect();
+        sorted.sort_unstable();
+        Self::of_sorted(&sorted)
+    }
+
+    // 昇順に並べ済みのサンプルから
+    pub fn of_sorted(sorted: &[u64]) -> Option<Self> {
+        Some(Self {
+            samples: sorted.len(),
+            p50: percentile(sorted, 50.0)?,
+            p90: percentile(sorted, 90.0)?,
+            p95: percentile(sorted, 95.0)?,
+            p99: percentile(sorted, 99.0)?,
+        })
+    }
+}
+
+// 昇順に並んだ sorted の p 百分位（nearest-rank）
+pub fn percentile(sorted: &[u64], p: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+// slo_percent の既定値
+pub const DEFAULT_SLO_PERCENT: f64 = 99.9;
+
+fn default_slo() -> f64 {
+    DEFAULT_SLO_PERCENT
+}
+
+// 失敗（Down / Error）したときの再試行の仕方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff_ms: u64,     // 1回目の再試行までの待ち時間（以降は倍々）
+    pub max_backoff_ms: u64, // 待ち時間の上限
+}
+
+impl RetryPolicy {
+    // n 回目（1 始まり）の再試行までの待ち時間。半分は固定、残り半分をランダムにして同時の再試行をばらけさせる
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .backoff_ms
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+            .min(self.max_backoff_ms);
+        let half = backoff / 2;
+        Duration::from_millis(half + rand::random_range(0..=backoff - half))
+    }
 }
 
-const MAX_HISTORY: usize = 50;
+// 生の結果から状態を切り替えるまでの回数と、flap 判定の条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Thresholds {
+    pub fail_after: u32,     // 連続でこの回数 NG なら Down（許容時間超過も同様）
+    pub recover_after: u32,  // 連続でこの回数 OK なら復旧
+    pub flap_threshold: u32, // flap_window_seconds 内の OK⇔NG の入れ替わりがこの回数以上なら flapping（0 で無効）
+    pub flap_window_seconds: u64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            fail_after: 1,
+            recover_after: 1,
+            flap_threshold: 0,
+            flap_window_seconds: 600,
+        }
+    }
+}
+
+// 状態の変化（通知のきっかけ）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateChange {
+    Down,
+    Recovered,
+    SlaBreach,
+    SlaRecovered,
+    Flapping,
+    FlappingStopped,
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+// 生の結果を Thresholds でならした、ターゲットとしての状態
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetState {
+    pub down: bool,
+    pub sla_breached: bool,
+    pub flapping: bool, // flapping の間は down / sla_breached を切り替えない
+    pub since: Option<DateTime<Utc>>, // down / up が最後に切り替わった日時
+    pub consecutive_failures: u32,
+    pub consecutive_successes: u32,
+    pub consecutive_breaches: u32,
+    pub consecutive_within: u32,        // 許容時間内に収まった回数
+    pub flips: VecDeque<DateTime<Utc>>, // 生の結果が OK⇔NG で入れ替わった日時
+    // latency_sla の窓（なければ直近 MAX_HISTORY 回）の百分位
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percentiles: Option<Percentiles>,
+}
+
+impl TargetState {
+    fn update(
+        &mut self,
+        previous: Option<&CheckResult>,
+        result: &CheckResult,
+        thresholds: &Thresholds,
+    ) -> Vec<StateChange> {
+        let failing = result.is_failing();
+        let breached = result.is_breached();
+        let fail_after = thresholds.fail_after.max(1);
+        let recover_after = thresholds.recover_after.max(1);
+        let mut changes = Vec::new();
+
+        if failing {
+            self.consecutive_failures += 1;
+            self.consecutive_successes = 0;
+            self.consecutive_breaches = 0;
+            self.consecutive_within = 0;
+        } else {
+            self.consecutive_successes += 1;
+            self.consecutive_failures = 0;
+            if breached {
+                self.consecutive_breaches += 1;
+                self.consecutive_within = 0;
+            } else {
+                self.consecutive_within += 1;
+                self.consecutive_breaches = 0;
+            }
+        }
+
+        // flap 判定（窓の外に出た入れ替わりは捨てる）
+        if previous.is_some_and(|p| p.is_failing() != failing) {
+            self.flips.push_back(result.timestamp);
+        }
+        let window = chrono::Duration::seconds(thresholds.flap_window_seconds as i64);
+        while self
+            .flips
+            .front()
+            .is_some_and(|at| result.timestamp - *at > window)
+        {
+            self.flips.pop_front();
+        }
+        let flapping =
+            thresholds.flap_threshold > 0 && self.flips.len() as u32 >= thresholds.flap_threshold;
+        if flapping != self.flapping {
+            self.flapping = flapping;
+            changes.push(if flapping {
+                StateChange::Flapping
+            } else {
+                StateChange::FlappingStopped
+            });
+        }
+        if self.flapping {
+            return changes;
+        }
+
+        if !self.down && self.consecutive_failures >= fail_after {
+            self.down = true;
+            self.since = Some(result.timestamp);
+            changes.push(StateChange::Down);
+        } else if self.down && self.consecutive_successes >= recover_after {
+            self.down = false;
+            self.since = Some(result.timestamp);
+            changes.push(StateChange::Recovered);
+        }
+
+        // Down の間は許容時間の判定はしない（Down の通知だけにする）
+        if self.down {
+            self.sla_breached = false;
+        } else if !self.sla_breached && self.consecutive_breaches >= fail_after {
+            self.sla_breached = true;
+            changes.push(StateChange::SlaBreach);
+        } else if self.sla_breached && self.consecutive_within >= recover_after {
+            self.sla_breached = false;
+            changes.push(StateChange::SlaRecovered);
+        }
+
+        changes
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CheckHistory {
     pub url: String,
+    // 設定の name / group / tags（name があればそれが histories のキー）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     #[serde(default)] // ← 古いstatus.json対策
     pub acceptable_latency_ms: u64, // このURLの許容時間
+    #[serde(default)]
+    pub kind: CheckKind,
+    #[serde(default)]
+    pub latency_phase: LatencyPhase, // 許容時間を適用する区間
+    #[serde(default)]
+    pub request: RequestSpec,
+    #[serde(default)]
+    pub certificate_chain: Vec<CertificateInfo>, // 最後に受け取った証明書チェーン
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_sla: Option<LatencySla>, // 許容時間を百分位で判定する
+    #[serde(default = "default_slo")]
+    pub slo_percent: f64, // 稼働率の目標（error budget の計算用）
+    #[serde(default)]
+    pub state: TargetState,
+    // 設定から消えた日時。チェックはせず、retired_retention_days の間だけ履歴を残す
+    #[serde(default)]
+    pub retired_at: Option<DateTime<Utc>>,
     pub results: VecDeque<CheckResult>,
+    // 百分位の窓（日時, ミリ秒）。保存せず、起動時に直近の結果から埋め直す
+    #[serde(skip)]
+    latencies: VecDeque<(DateTime<Utc>, u64)>,
 }
 
 impl CheckHistory {
@@ -35,29 +458,296 @@ impl CheckHistory {
     pub fn new(url: String, acceptable_latency_ms: u64) -> Self {
         Self {
             url,
+            name: None,
+            group: None,
+            tags: Vec::new(),
             acceptable_latency_ms,
+            kind: CheckKind::default(),
+            latency_phase: LatencyPhase::default(),
+            request: RequestSpec::default(),
+            certificate_chain: Vec::new(),
+            thresholds: Thresholds::default(),
+            latency_sla: None,
+            slo_percent: DEFAULT_SLO_PERCENT,
+            state: TargetState::default(),
+            retired_at: None,
             results: VecDeque::with_capacity(MAX_HISTORY),
+            latencies: VecDeque::new(),
         }
     }
 
-    pub fn push(&mut self, mut result: CheckResult) {
+    // histories のキー（name、なければ URL）
+    pub fn key(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn is_retired(&self) -> bool {
+        self.retired_at.is_some()
+    }
+
+    // 結果を積み、状態が変わった場合はその内容を返す
+    pub fn push(&mut self, mut result: CheckResult) -> Vec<StateChange> {
+        if !result.certificates.is_empty() {
+            self.certificate_chain = std::mem::take(&mut result.certificates);
+        }
+
         // 許容時間との差分を計算（プラスなら超過、マイナスなら余裕あり）
-        if let Some(curr_rt) = result.response_time {
+        if let Some(curr_rt) = result.latency(self.latency_phase) {
             let diff = curr_rt.as_millis() as i128 - self.acceptable_latency_ms as i128;
             result.diff_from_acceptable = Some(diff);
         }
 
+        // latency_sla があれば、今回を含む窓の百分位と許容時間との差分にする
+        self.sample(&result);
+        let sorted = self.update_percentiles();
+        if let Some(sla) = &self.latency_sla
+            && !result.is_failing()
+            && let Some(p) = percentile(&sorted, sla.percentile)
+        {
+            result.diff_from_acceptable = Some(p as i128 - self.acceptable_latency_ms as i128);
+        }
+
         // 前回との差分を計算
-        if let Some(prev) = self.results.back() {
-            if let (Some(prev_rt), Some(curr_rt)) = (prev.response_time, result.response_time) {
-                let diff = curr_rt.as_millis() as i128 - prev_rt.as_millis() as i128;
-                result.diff_from_prev = Some(diff);
-            }
+        if let Some(prev) = self.results.back()
+            && let (Some(prev_rt), Some(curr_rt)) = (prev.response_time, result.response_time)
+        {
+            let diff = curr_rt.as_millis() as i128 - prev_rt.as_millis() as i128;
+            result.diff_from_prev = Some(diff);
         }
 
+        let changes = self
+            .state
+            .update(self.results.back(), &result, &self.thresholds);
+
         if self.results.len() == MAX_HISTORY {
             self.results.pop_front();
         }
         self.results.push_back(result);
+        changes
+    }
+
+    // 百分位の窓を results（古い順）で埋め直す（状態や履歴は変えない）
+    pub fn seed_latencies<'a>(&mut self, results: impl IntoIterator<Item = &'a CheckResult>) {
+        self.latencies.clear();
+        for result in results {
+            self.sample(result);
+        }
+        self.update_percentiles();
+    }
+
+    // 窓の百分位を計算し直し、並べ替えた所要時間（ミリ秒）を返す
+    fn update_percentiles(&mut self) -> Vec<u64> {
+        let mut sorted: Vec<u64> = self.latencies.iter().map(|&(_, ms)| ms).collect();
+        sorted.sort_unstable();
+        self.state.percentiles = Percentiles::of_sorted(&sorted);
+        sorted
+    }
+
+    // 成功したチェックの所要時間を窓に入れ、窓から外れたものを捨てる
+    fn sample(&mut self, result: &CheckResult) {
+        if !result.is_failing()
+            && let Some(latency) = result.latency(self.latency_phase)
+        {
+            self.latencies
+                .push_back((result.timestamp, latency.as_millis() as u64));
+        }
+
+        let (checks, minutes) = match &self.latency_sla {
+            Some(sla) => (sla.checks, sla.minutes),
+            None => (Some(MAX_HISTORY), None),
+        };
+        let limit = checks
+            .unwrap_or(MAX_LATENCY_SAMPLES)
+            .min(MAX_LATENCY_SAMPLES);
+        while self.latencies.len() > limit {
+            self.latencies.pop_front();
+        }
+        if let Some(minutes) = minutes {
+            let from = result.timestamp - chrono::Duration::minutes(minutes as i64);
+            while self.latencies.front().is_some_and(|&(at, _)| at <= from) {
+                self.latencies.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(thresholds: Thresholds) -> CheckHistory {
+        let mut history = CheckHistory::new("https://example.com".to_string(), 100);
+        history.thresholds = thresholds;
+        history
+    }
+
+    // start から seconds 秒後の、ms ミリ秒かかった結果
+    fn result(status: CheckStatus, ms: u64, seconds: i64) -> CheckResult {
+        let start = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        CheckResult {
+            timestamp: start + chrono::Duration::seconds(seconds),
+            response_time: Some(Duration::from_millis(ms)),
+            ..CheckResult::new(status)
+        }
+    }
+
+    fn push(history: &mut CheckHistory, status: CheckStatus, ms: u64, at: i64) -> Vec<StateChange> {
+        history.push(result(status, ms, at))
+    }
+
+    fn thresholds(fail_after: u32, recover_after: u32) -> Thresholds {
+        Thresholds {
+            fail_after,
+            recover_after,
+            ..Thresholds::default()
+        }
+    }
+
+    #[test]
+    fn down_after_consecutive_failures_and_recovered_after_successes() {
+        let mut history = history(thresholds(3, 2));
+
+        assert!(push(&mut history, CheckStatus::Error, 10, 0).is_empty());
+        assert!(push(&mut history, CheckStatus::Down, 10, 10).is_empty());
+        // 途中で成功すると数え直し
+        assert!(push(&mut history, CheckStatus::Up, 10, 20).is_empty());
+        assert!(push(&mut history, CheckStatus::Down, 10, 30).is_empty());
+        assert!(push(&mut history, CheckStatus::AssertionFailed, 10, 40).is_empty());
+        assert_eq!(
+            push(&mut history, CheckStatus::Error, 10, 50),
+            [StateChange::Down]
+        );
+        assert!(push(&mut history, CheckStatus::Error, 10, 60).is_empty());
+
+        assert!(push(&mut history, CheckStatus::Up, 10, 70).is_empty());
+        // Warning は成功として数える
+        assert_eq!(
+            push(&mut history, CheckStatus::Warning, 10, 80),
+            [StateChange::Recovered]
+        );
+        assert!(!history.state.down);
+        assert_eq!(
+            history.state.since,
+            Some(result(CheckStatus::Up, 0, 80).timestamp)
+        );
+    }
+
+    #[test]
+    fn sla_breach_needs_consecutive_slow_successes() {
+        let mut history = history(thresholds(2, 2));
+
+        assert!(push(&mut history, CheckStatus::Up, 150, 0).is_empty());
+        assert_eq!(
+            push(&mut history, CheckStatus::Up, 150, 10),
+            [StateChange::SlaBreach]
+        );
+        assert!(push(&mut history, CheckStatus::Up, 50, 20).is_empty());
+        assert!(push(&mut history, CheckStatus::Up, 150, 30).is_empty());
+        assert!(push(&mut history, CheckStatus::Up, 50, 40).is_empty());
+        assert_eq!(
+            push(&mut history, CheckStatus::Up, 50, 50),
+            [StateChange::SlaRecovered]
+        );
+    }
+
+    #[test]
+    fn down_clears_sla_breach_without_notifying() {
+        let mut history = history(thresholds(1, 1));
+        assert_eq!(
+            history.push(result(CheckStatus::Up, 150, 0)),
+            [StateChange::SlaBreach]
+        );
+        assert_eq!(
+            history.push(result(CheckStatus::Down, 10, 10)),
+            [StateChange::Down]
+        );
+        assert!(!history.state.sla_breached);
+        assert_eq!(
+            history.push(result(CheckStatus::Up, 10, 20)),
+            [StateChange::Recovered]
+        );
+    }
+
+    #[test]
+    fn flapping_holds_state_changes_until_flips_leave_the_window() {
+        let mut history = history(Thresholds {
+            flap_threshold: 3,
+            flap_window_seconds: 60,
+            ..Thresholds::default()
+        });
+
+        assert!(push(&mut history, CheckStatus::Up, 10, 0).is_empty());
+        assert_eq!(
+            push(&mut history, CheckStatus::Down, 10, 10),
+            [StateChange::Down]
+        );
+        assert_eq!(
+            push(&mut history, CheckStatus::Up, 10, 20),
+            [StateChange::Recovered]
+        );
+        // 3回目の入れ替わりで flapping になり、Down は通知しない
+        assert_eq!(
+            push(&mut history, CheckStatus::Down, 10, 30),
+            [StateChange::Flapping]
+        );
+        assert!(push(&mut history, CheckStatus::Up, 10, 40).is_empty());
+        assert!(push(&mut history, CheckStatus::Down, 10, 50).is_empty());
+        assert!(history.state.flapping);
+        assert!(!history.state.down);
+
+        // 窓から入れ替わりが外れると flapping が終わり、今の状態が反映される
+        assert_eq!(
+            push(&mut history, CheckStatus::Down, 10, 200),
+            [StateChange::FlappingStopped, StateChange::Down]
+        );
+        assert!(history.state.flips.is_empty());
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let sorted: Vec<u64> = (1..=10).map(|n| n * 10).collect();
+        assert_eq!(percentile(&sorted, 50.0), Some(50));
+        assert_eq!(percentile(&sorted, 90.0), Some(90));
+        assert_eq!(percentile(&sorted, 95.0), Some(100));
+        assert_eq!(percentile(&sorted, 0.0), Some(10));
+        assert_eq!(percentile(&sorted, 100.0), Some(100));
+        assert_eq!(percentile(&[], 50.0), None);
+
+        let p = Percentiles::of([300, 100, 200]).unwrap();
+        assert_eq!((p.samples, p.p50, p.p90, p.p99), (3, 200, 300, 300));
+        assert_eq!(Percentiles::of([]), None);
+    }
+
+    #[test]
+    fn latency_sla_compares_the_window_percentile() {
+        let mut history = history(Thresholds::default());
+        history.latency_sla = Some(LatencySla {
+            percentile: 50.0,
+            checks: Some(3),
+            minutes: None,
+        });
+
+        push(&mut history, CheckStatus::Up, 50, 0);
+        push(&mut history, CheckStatus::Up, 300, 10);
+        // 失敗は窓に入れない
+        push(&mut history, CheckStatus::Down, 900, 20);
+        push(&mut history, CheckStatus::Up, 120, 30);
+        // 窓は [50, 300, 120] で p50 は 120
+        assert_eq!(history.state.percentiles.unwrap().p50, 120);
+        assert_eq!(
+            history.results.back().unwrap().diff_from_acceptable,
+            Some(20)
+        );
+
+        push(&mut history, CheckStatus::Up, 60, 40);
+        // 50 が窓から外れて [300, 120, 60]
+        let p = history.state.percentiles.unwrap();
+        assert_eq!((p.samples, p.p50, p.p99), (3, 120, 300));
     }
 }
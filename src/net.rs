@@ -0,0 +1,209 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::error::ProtoErrorKind;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::model::{CertificateInfo, CheckResult, ErrorKind};
+
+// 接続の各段階（DNS 解決 → TCP 接続 → TLS ハンドシェイク）で起きたエラー
+#[derive(Debug)]
+pub struct NetError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl NetError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<NetError> for CheckResult {
+    fn from(e: NetError) -> Self {
+        CheckResult::error(e.kind, e.message)
+    }
+}
+
+// OS の証明書ストアを信頼する TLS 設定
+pub fn tls_config() -> anyhow::Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    let (added, _) = roots.add_parsable_certificates(native.certs);
+    if added == 0 {
+        log::warn!("no trusted root certificates were loaded");
+    }
+
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+    Ok(config)
+}
+
+// システムの設定（resolv.conf / hosts）で名前解決する。所要時間を測るためキャッシュは使わない
+pub async fn resolve(host: &str, port: u16) -> Result<(Vec<SocketAddr>, Duration), NetError> {
+    let start = Instant::now();
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok((vec![SocketAddr::new(ip, port)], start.elapsed()));
+    }
+
+    let (config, mut opts) = hickory_resolver::system_conf::read_system_conf()
+        .map_err(|e| NetError::new(ErrorKind::Dns, format!("{}: {}", host, e)))?;
+    opts.cache_size = 0;
+    let resolver = TokioAsyncResolver::tokio(config, opts);
+
+    let addrs: Vec<SocketAddr> = resolver
+        .lookup_ip(host)
+        .await
+        .map_err(|e| {
+            let message = match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                    format!("{}: no records ({})", host, response_code)
+                }
+                _ => format!("{}: {}", host, e),
+            };
+            NetError::new(resolve_error_kind(&e), message)
+        })?
+        .iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(NetError::new(
+            ErrorKind::Dns,
+            format!("{}: no addresses found", host),
+        ));
+    }
+    Ok((addrs, start.elapsed()))
+}
+
+// 名前解決のエラーを種類で分ける（応答がなかったものだけ Timeout）
+pub fn resolve_error_kind(err: &ResolveError) -> ErrorKind {
+    match err.kind() {
+        ResolveErrorKind::Timeout => ErrorKind::Timeout,
+        ResolveErrorKind::Proto(e) if matches!(e.kind(), ProtoErrorKind::Timeout) => {
+            ErrorKind::Timeout
+        }
+        _ => ErrorKind::Dns,
+    }
+}
+
+// 解決したアドレスを順に試し、最初に繋がったものを使う
+pub async fn connect(addrs: &[SocketAddr]) -> Result<(TcpStream, Duration), NetError> {
+    let start = Instant::now();
+    let mut last_err = None;
+
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok((stream, start.elapsed())),
+            Err(e) => last_err = Some((*addr, e)),
+        }
+    }
+
+    let (addr, err) = last_err.expect("connect() requires at least one address");
+    let kind = match err.kind() {
+        std::io::ErrorKind::ConnectionRefused => ErrorKind::ConnectRefused,
+        std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+        _ => ErrorKind::Connect,
+    };
+    Err(NetError::new(kind, format!("{}: {}", addr, err)))
+}
+
+pub async fn tls_handshake(
+    connector: &TlsConnector,
+    stream: TcpStream,
+    host: &str,
+) -> Result<(TlsStream<TcpStream>, Duration), NetError> {
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|e| NetError::new(ErrorKind::Tls, format!("{}: {}", host, e)))?;
+
+    let start = Instant::now();
+    let stream = connector
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| NetError::new(io_error_kind(&e), format!("{}: {}", host, e)))?;
+
+    Ok((stream, start.elapsed()))
+}
+
+// 接続中の I/O エラーを分類する。rustls のエラー（証明書・プロトコル）と途中で切られたハンドシェイクは Tls
+pub fn io_error_kind(err: &std::io::Error) -> ErrorKind {
+    if err.get_ref().is_some_and(|e| e.is::<rustls::Error>()) {
+        return ErrorKind::Tls;
+    }
+    match err.kind() {
+        std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+        std::io::ErrorKind::UnexpectedEof => ErrorKind::Tls,
+        _ => ErrorKind::Connect,
+    }
+}
+
+// ハンドシェイク済みの接続からサーバ証明書チェーンを取り出す
+pub fn peer_certificates(stream: &TlsStream<TcpStream>) -> Vec<CertificateInfo> {
+    let Some(chain) = stream.get_ref().1.peer_certificates() else {
+        return Vec::new();
+    };
+
+    chain
+        .iter()
+        .filter_map(|der| {
+            let (_, cert) = X509Certificate::from_der(der.as_ref()).ok()?;
+            let not_after =
+                chrono::DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)?;
+
+            let sans = match cert.subject_alternative_name() {
+                Ok(Some(ext)) => ext
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(dns) => Some(dns.to_string()),
+                        GeneralName::IPAddress(bytes) => ip_from_bytes(bytes),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            Some(CertificateInfo {
+                subject: cert.subject().to_string(),
+                issuer: cert.issuer().to_string(),
+                sans,
+                not_after,
+            })
+        })
+        .collect()
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<String> {
+    let ip = match bytes.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(ip.to_string())
+}
@@ -1,6 +1,13 @@
 use crate::model::CheckResult;
 
 pub fn print_log(url: &str, result: &CheckResult) {
+    // 再試行した場合だけ試行回数を付ける
+    let attempts = if result.attempts > 1 {
+        format!(" attempts={}", result.attempts)
+    } else {
+        String::new()
+    };
+
     match result.status {
         crate::model::CheckStatus::Up => {
             let diff_str = match result.diff_from_prev {
@@ -9,20 +16,46 @@ pub fn print_log(url: &str, result: &CheckResult) {
             };
 
             println!(
-                "[OK] {} response_time={}ms diff_from_prev={}",
+                "[OK] {} response_time={}ms diff_from_prev={}{}",
                 url,
-                result
-                    .response_time
-                    .map(|d| d.as_millis())
-                    .unwrap_or(0),
-                diff_str
+                result.response_time.map(|d| d.as_millis()).unwrap_or(0),
+                diff_str,
+                attempts
             );
         }
         crate::model::CheckStatus::Down => {
-            println!("[NG] {} down", url);
+            println!(
+                "[NG] {} down status={}{}",
+                url,
+                result
+                    .status_code
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                attempts
+            );
+        }
+        crate::model::CheckStatus::Error => match (&result.error_kind, &result.error_message) {
+            (Some(kind), Some(message)) => {
+                println!("[ERR] {} {:?}: {}{}", url, kind, message, attempts)
+            }
+            _ => println!("[ERR] {} error{}", url, attempts),
+        },
+        crate::model::CheckStatus::Warning => {
+            println!(
+                "[WARN] {} {}",
+                url,
+                result.failure_reason.as_deref().unwrap_or("warning")
+            );
         }
-        crate::model::CheckStatus::Error => {
-            println!("[ERR] {} error", url);
+        crate::model::CheckStatus::AssertionFailed => {
+            println!(
+                "[FAIL] {} {}",
+                url,
+                result
+                    .failure_reason
+                    .as_deref()
+                    .unwrap_or("assertion failed")
+            );
         }
     }
 }
@@ -0,0 +1,40 @@
+use std::io;
+use std::path::PathBuf;
+
+// status.json / ruliadema.log / breaches.json / incidents.json / ruliadema.db を置くディレクトリ（--data-dir / RULIADEMA_DATA_DIR）
+#[derive(Debug, Clone)]
+pub struct DataDir {
+    dir: PathBuf,
+}
+
+impl DataDir {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    // daemon 起動時にディレクトリがなければ作る
+    pub fn create(&self) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)
+    }
+
+    pub fn status(&self) -> PathBuf {
+        self.dir.join("status.json")
+    }
+
+    pub fn log(&self) -> PathBuf {
+        self.dir.join("ruliadema.log")
+    }
+
+    pub fn breaches(&self) -> PathBuf {
+        self.dir.join("breaches.json")
+    }
+
+    pub fn incidents(&self) -> PathBuf {
+        self.dir.join("incidents.json")
+    }
+
+    // [storage] backend = "sqlite" のときの保存先
+    pub fn database(&self) -> PathBuf {
+        self.dir.join("ruliadema.db")
+    }
+}
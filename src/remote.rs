@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::incident::Incident;
+use crate::model::CheckHistory;
+use crate::report::{Availability, TargetReport};
+use crate::store::Rollup;
+
+// daemon との接続状態（view のフッターに表示する）
+#[derive(Debug, Clone)]
+pub enum Connection {
+    Connecting,
+    Connected { since: DateTime<Utc> },
+    Disconnected { error: String, retry_in: Duration },
+}
+
+// daemon の API から受け取った内容（ローカルの status.json / incidents.json / breaches.json / config に相当）
+#[derive(Debug, Clone)]
+pub struct RemoteData {
+    pub histories: BTreeMap<String, CheckHistory>,
+    pub incidents: Vec<Incident>,
+    pub breaches: Vec<Value>,
+    pub config_path: String,
+    pub config: String,
+    pub connection: Connection,
+    rollups: Option<(String, Vec<Rollup>)>, // 最後に取得したまとめ（問い合わせ内容, 結果）
+    report: Option<(String, Vec<Availability>)>, // 最後に取得した稼働率（ターゲット, 期間ごとの結果）
+}
+
+// view --remote: 別スレッドで /api/events を購読し、受け取った変化を RemoteData に反映し続ける
+pub struct Remote {
+    pub base: String,
+    data: Arc<Mutex<RemoteData>>,
+    client: reqwest::Client,
+    runtime: tokio::runtime::Handle,
+    requested: Mutex<Option<(String, Instant)>>,
+    reported: Mutex<Option<(String, Instant)>>,
+}
+
+// キープアライブ（15秒ごと）が届かなければ切れたとみなす
+const READ_TIMEOUT: Duration = Duration::from_secs(45);
+const MAX_RETRY: Duration = Duration::from_secs(30);
+// まとめ・稼働率を取り直す間隔
+const ROLLUP_REFRESH: Duration = Duration::from_secs(10);
+const REPORT_REFRESH: Duration = Duration::from_secs(30);
+// ターゲットごとに表示する違反記録の上限（新しい方から。ローカルの view と同じ）
+pub const BREACH_LIMIT: usize = 1000;
+
+impl Remote {
+    pub fn connect(base: &str) -> anyhow::Result<Self> {
+        let base = base.trim_end_matches('/').to_string();
+        url::Url::parse(&base).with_context(|| format!("invalid --remote URL {:?}", base))?;
+
+        let data = Arc::new(Mutex::new(RemoteData {
+            histories: BTreeMap::new(),
+            incidents: Vec::new(),
+            breaches: Vec::new(),
+            config_path: String::new(),
+            config: String::new(),
+            connection: Connection::Connecting,
+            rollups: None,
+            report: None,
+        }));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .build()?;
+
+        let handle = runtime.handle().clone();
+        let (thread_client, thread_base, thread_data) =
+            (client.clone(), base.clone(), data.clone());
+        std::thread::spawn(move || runtime.block_on(run(thread_client, thread_base, thread_data)));
+        Ok(Self {
+            base,
+            data,
+            client,
+            runtime: handle,
+            requested: Mutex::new(None),
+            reported: Mutex::new(None),
+        })
+    }
+
+    // 直近 span の間を seconds 秒ごとにまとめたもの。取得済みのものを返し、古ければ裏で取り直す
+    pub fn rollups(
+        &self,
+        target: &str,
+        span: chrono::Duration,
+        seconds: u64,
+    ) -> Option<Vec<Rollup>> {
+        let key = format!("{} {} {}", target, span.num_seconds(), seconds);
+        if stale(&self.requested, &key, ROLLUP_REFRESH) {
+            let query = vec![
+                ("target", target.to_string()),
+                ("from", (Utc::now() - span).to_rfc3339()),
+                ("seconds", seconds.to_string()),
+            ];
+            let key = key.clone();
+            self.spawn_fetch("/api/rollups", query, move |d, rollups: Vec<Rollup>| {
+                d.rollups = Some((key, rollups));
+            });
+        }
+
+        let data = self.data.lock().unwrap_or_else(|e| e.into_inner());
+        data.rollups
+            .as_ref()
+            .filter(|(k, _)| *k == key)
+            .map(|(_, rollups)| rollups.clone())
+    }
+
+    // 1ターゲットの既定の期間ごとの稼働率。取得済みのものを返し、古ければ裏で取り直す
+    pub fn report(&self, target: &str) -> Option<Vec<Availability>> {
+        if stale(&self.reported, target, REPORT_REFRESH) {
+            let key = target.to_string();
+            let query = vec![("target", key.clone())];
+            self.spawn_fetch(
+                "/api/report",
+                query,
+                move |d, reports: Vec<TargetReport>| {
+                    if let Some(report) = reports.into_iter().next() {
+                        d.report = Some((key, report.windows));
+                    }
+                },
+            );
+        }
+
+        let data = self.data.lock().unwrap_or_else(|e| e.into_inner());
+        data.report
+            .as_ref()
+            .filter(|(k, _)| k == target)
+            .map(|(_, windows)| windows.clone())
+    }
+
+    // path を裏で取得し、届いたら apply で RemoteData に反映する（失敗したら次の取り直しを待つ）
+    fn spawn_fetch<T: serde::de::DeserializeOwned + Send + 'static>(
+        &self,
+        path: &'static str,
+        query: Vec<(&'static str, String)>,
+        apply: impl FnOnce(&mut RemoteData, T) + Send + 'static,
+    ) {
+        let (client, base, data) = (self.client.clone(), self.base.clone(), self.data.clone());
+        self.runtime.spawn(async move {
+            let value: anyhow::Result<T> = fetch(&client, &base, path, &query).await;
+            if let (Ok(value), Ok(mut d)) = (value, data.lock()) {
+                apply(&mut d, value);
+            }
+        });
+    }
+
+    // 描画用に今の内容を複製して返す
+    pub fn snapshot(&self) -> RemoteData {
+        self.data.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+// key を最後に要求してから refresh 以上経っていれば（別の key なら常に）要求した日時を更新して true
+fn stale(requested: &Mutex<Option<(String, Instant)>>, key: &str, refresh: Duration) -> bool {
+    let Ok(mut requested) = requested.lock() else {
+        return false;
+    };
+    let stale = requested
+        .as_ref()
+        .is_none_or(|(k, at)| k != key || at.elapsed() >= refresh);
+    if stale {
+        *requested = Some((key.to_string(), Instant::now()));
+    }
+    stale
+}
+
+// 切れたら待ち時間を倍々に延ばしながら繋ぎ直す
+async fn run(client: reqwest::Client, base: String, data: Arc<Mutex<RemoteData>>) {
+    let mut retry_in = Duration::from_secs(1);
+    loop {
+        set_connection(&data, Connection::Connecting);
+        let error = match subscribe(&client, &base, &data).await {
+            Ok(()) => "connection closed by the daemon".to_string(),
+            Err(e) => format!("{:#}", e),
+        };
+        // 一度繋がっていれば待ち時間を戻す
+        if matches!(connection(&data), Some(Connection::Connected { .. })) {
+            retry_in = Duration::from_secs(1);
+        }
+        set_connection(&data, Connection::Disconnected { error, retry_in });
+        tokio::time::sleep(retry_in).await;
+        retry_in = (retry_in * 2).min(MAX_RETRY);
+    }
+}
+
+fn connection(data: &Mutex<RemoteData>) -> Option<Connection> {
+    data.lock().ok().map(|d| d.connection.clone())
+}
+
+fn set_connection(data: &Mutex<RemoteData>, connection: Connection) {
+    if let Ok(mut d) = data.lock() {
+        d.connection = connection;
+    }
+}
+
+async fn subscribe(
+    client: &reqwest::Client,
+    base: &str,
+    data: &Mutex<RemoteData>,
+) -> anyhow::Result<()> {
+    let mut response = client
+        .get(format!("{}/api/events", base))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    loop {
+        let chunk = tokio::time::timeout(READ_TIMEOUT, response.chunk())
+            .await
+            .context("no data from the daemon")??;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+        buffer.extend_from_slice(&chunk);
+
+        // SSE のフレームは空行で区切られる（チャンクの境目で文字が割れないようバイト列のまま探す）
+        while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+            let frame: Vec<u8> = buffer.drain(..end + 2).collect();
+            let frame = String::from_utf8_lossy(&frame);
+            let (event, payload) = parse_frame(&frame);
+            if let Some(event) = event {
+                apply(client, base, data, event, &payload).await?;
+            }
+        }
+    }
+}
+
+// "event: name\ndata: ..." を (name, data) にする。コメント（": keepalive"）だけなら None
+fn parse_frame(frame: &str) -> (Option<&str>, String) {
+    let mut event = None;
+    let mut payload = Vec::new();
+    for line in frame.lines() {
+        if let Some(name) = line.strip_prefix("event:") {
+            event = Some(name.trim());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            payload.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    (event, payload.join("\n"))
+}
+
+async fn apply(
+    client: &reqwest::Client,
+    base: &str,
+    data: &Mutex<RemoteData>,
+    event: &str,
+    payload: &str,
+) -> anyhow::Result<()> {
+    match event {
+        // 接続直後と設定の読み直し後。障害・違反・設定も取り直す
+        "status" => {
+            let histories: BTreeMap<String, CheckHistory> = serde_json::from_str(payload)?;
+            let incidents: Vec<Incident> = fetch(client, base, "/api/incidents", &[]).await?;
+            // 上限はターゲットごとなので、ターゲットごとに取る（まとめて取ると多いターゲットに押し出される）
+            let mut breaches: Vec<Value> = Vec::new();
+            for key in histories.keys() {
+                let query = [("target", key.clone()), ("limit", BREACH_LIMIT.to_string())];
+                breaches.extend(fetch::<Vec<Value>>(client, base, "/api/breaches", &query).await?);
+            }
+            let config: Value = fetch(client, base, "/api/config", &[])
+                .await
+                .unwrap_or_default();
+
+            let Ok(mut d) = data.lock() else {
+                bail!("state is unavailable");
+            };
+            d.histories = histories;
+            d.incidents = incidents;
+            d.breaches = breaches;
+            d.config_path = config["path"].as_str().unwrap_or_default().to_string();
+            d.config = config["content"]
+                .as_str()
+                .unwrap_or("Error: the daemon did not return its config.")
+                .to_string();
+            if !matches!(d.connection, Connection::Connected { .. }) {
+                d.connection = Connection::Connected { since: Utc::now() };
+            }
+        }
+        "history" => {
+            let history: CheckHistory = serde_json::from_str(payload)?;
+            if let Ok(mut d) = data.lock() {
+                d.histories.insert(history.key().to_string(), history);
+            }
+        }
+        // 新しい障害は追加し、既存のもの（同じ id）は置き換える
+        "incident" => {
+            let incident: Incident = serde_json::from_str(payload)?;
+            if let Ok(mut d) = data.lock() {
+                match d.incidents.iter_mut().find(|i| i.id == incident.id) {
+                    Some(existing) => *existing = incident,
+                    None => d.incidents.push(incident),
+                }
+            }
+        }
+        "breach" => {
+            let breach: Value = serde_json::from_str(payload)?;
+            if let Ok(mut d) = data.lock() {
+                // 同じターゲットの記録が上限を超えたら古いものから捨てる
+                let key = breach_key(&breach);
+                if d.breaches.iter().filter(|b| breach_key(b) == key).count() >= BREACH_LIMIT
+                    && let Some(oldest) = d.breaches.iter().position(|b| breach_key(b) == key)
+                {
+                    d.breaches.remove(oldest);
+                }
+                d.breaches.push(breach);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// 違反記録のターゲットのキー（name のあるターゲットの記録は name を持つ）
+pub fn breach_key(breach: &Value) -> Option<&str> {
+    breach["name"].as_str().or(breach["url"].as_str())
+}
+
+async fn fetch<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    base: &str,
+    path: &str,
+    query: &[(&str, String)],
+) -> anyhow::Result<T> {
+    let value = client
+        .get(format!("{}{}", base, path))
+        .query(query)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .with_context(|| format!("GET {}", path))?;
+    Ok(value)
+}
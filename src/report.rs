@@ -0,0 +1,319 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::CheckHistory;
+use crate::store::{Rollup, Store, TimeRange};
+
+// 指定がなければ集計する期間
+pub const DEFAULT_WINDOWS: [&str; 4] = ["1h", "24h", "7d", "30d"];
+
+// 集計に使うまとめの単位（期間の始まりはこの精度で切り上げる）
+const BUCKET_SECONDS: u64 = 60;
+
+// 1ターゲット・1期間の稼働率
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Availability {
+    pub window: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub checks: u64,
+    pub failures: u64, // Down / Error / AssertionFailed
+    pub breaches: u64, // 成功したが許容時間を超えた
+    // 失敗しなかったチェックの割合（チェックがなければ None）
+    pub uptime_percent: Option<f64>,
+    // 許容時間内に成功したチェックの割合
+    pub sla_percent: Option<f64>,
+    pub slo_percent: f64,
+    // 期間中に許される失敗（チェックの 100 - slo_percent %）のうち残っている割合。使い切ると負になる
+    pub error_budget_remaining_percent: Option<f64>,
+}
+
+impl Availability {
+    fn new(
+        window: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        rollups: &[&Rollup],
+        slo_percent: f64,
+    ) -> Self {
+        let checks: u64 = rollups.iter().map(|r| r.checks).sum();
+        let failures: u64 = rollups.iter().map(|r| r.failures).sum();
+        let breaches: u64 = rollups.iter().map(|r| r.breaches).sum();
+        let percent = |count: u64| (checks > 0).then(|| count as f64 * 100.0 / checks as f64);
+
+        let allowed = checks as f64 * (100.0 - slo_percent) / 100.0;
+        Self {
+            window: window.to_string(),
+            from,
+            to,
+            checks,
+            failures,
+            breaches,
+            uptime_percent: percent(checks - failures),
+            sla_percent: percent(checks.saturating_sub(failures + breaches)),
+            slo_percent,
+            error_budget_remaining_percent: (checks > 0 && allowed > 0.0)
+                .then(|| (allowed - failures as f64) * 100.0 / allowed),
+        }
+    }
+}
+
+// 1ターゲットの期間ごとの稼働率（report / GET /api/report）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetReport {
+    pub target: String, // ターゲットのキー（name、なければ URL）
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub windows: Vec<Availability>,
+}
+
+impl TargetReport {
+    pub fn new(store: &dyn Store, history: &CheckHistory, windows: &[String]) -> Result<Self> {
+        Ok(Self {
+            target: history.key().to_string(),
+            url: history.url.clone(),
+            group: history.group.clone(),
+            tags: history.tags.clone(),
+            windows: availability(
+                store,
+                history.key(),
+                history.slo_percent,
+                windows,
+                Utc::now(),
+            )?,
+        })
+    }
+}
+
+// "90m" / "24h" / "7d" / "2w" を期間にする
+pub fn parse_window(window: &str) -> Result<Duration> {
+    let window = window.trim();
+    let split = window
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(window.len());
+    let (number, unit) = window.split_at(split);
+    let number: i64 = number
+        .parse()
+        .with_context(|| format!("window {:?}: expected a number and m / h / d / w", window))?;
+    let duration = match unit {
+        "m" => Duration::minutes(number),
+        "h" => Duration::hours(number),
+        "d" => Duration::days(number),
+        "w" => Duration::weeks(number),
+        _ => bail!("window {:?}: the unit must be m, h, d or w", window),
+    };
+    if number == 0 {
+        bail!("window {:?}: must be longer than 0", window);
+    }
+    Ok(duration)
+}
+
+// key の windows（now までの各期間）ごとの稼働率。一番長い期間のまとめを1回だけ読み、短い期間はその中から数える
+pub fn availability(
+    store: &dyn Store,
+    key: &str,
+    slo_percent: f64,
+    windows: &[String],
+    now: DateTime<Utc>,
+) -> Result<Vec<Availability>> {
+    let spans = windows
+        .iter()
+        .map(|w| parse_window(w).map(|span| (w, span)))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(longest) = spans.iter().map(|(_, span)| *span).max() else {
+        return Ok(Vec::new());
+    };
+    let range = TimeRange {
+        from: Some(window_start(now, longest)),
+        to: Some(now),
+        limit: None,
+    };
+    let rollups = store.rollups(key, &range, BUCKET_SECONDS)?;
+
+    Ok(spans
+        .into_iter()
+        .map(|(window, span)| {
+            // compact したまとめ（rollup_minutes 単位）が始まりをまたぐときは、そのまとめの始まりまで広げる
+            let from = window_start(now, span);
+            let from = rollups
+                .iter()
+                .find(|r| r.start < from && r.start + Duration::seconds(r.seconds as i64) > from)
+                .map_or(from, |r| r.start);
+            let inside: Vec<&Rollup> = rollups.iter().filter(|r| r.start >= from).collect();
+            Availability::new(window, from, now, &inside, slo_percent)
+        })
+        .collect())
+}
+
+// now - span を次のまとめの区切りに切り上げる（始まりをまたぐまとめは期間外の結果も含むので数えない）
+fn window_start(now: DateTime<Utc>, span: Duration) -> DateTime<Utc> {
+    let start = now - span;
+    let seconds = BUCKET_SECONDS as i64;
+    let floor = start.timestamp().div_euclid(seconds) * seconds;
+    let floor = DateTime::from_timestamp(floor, 0).unwrap_or(start);
+    if floor < start {
+        floor + Duration::seconds(seconds)
+    } else {
+        floor
+    }
+}
+
+// 稼働率などの表示（"99.95%"、チェックがなければ "-"）
+pub fn format_percent(percent: Option<f64>, decimals: usize) -> String {
+    percent.map_or("-".to_string(), |p| format!("{:.*}%", decimals, p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{CheckResult, CheckStatus};
+    use crate::store::{Retention, SqliteStore};
+    use serde_json::Value;
+
+    // まとめだけを返す保存先
+    struct Rollups(Vec<Rollup>);
+
+    impl Store for Rollups {
+        fn append_result(&mut self, _key: &str, _url: &str, _result: &CheckResult) -> Result<()> {
+            Ok(())
+        }
+
+        fn append_breach(&mut self, _key: &str, _entry: &Value) -> Result<()> {
+            Ok(())
+        }
+
+        fn results(&self, _key: &str, _range: &TimeRange) -> Result<Vec<CheckResult>> {
+            Ok(Vec::new())
+        }
+
+        fn breaches(&self, _key: Option<&str>, _range: &TimeRange) -> Result<Vec<Value>> {
+            Ok(Vec::new())
+        }
+
+        fn rollups(&self, _key: &str, range: &TimeRange, _seconds: u64) -> Result<Vec<Rollup>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|r| range.contains(r.start))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn result(at: &str, status: CheckStatus) -> CheckResult {
+        CheckResult {
+            timestamp: at.parse().unwrap(),
+            ..CheckResult::new(status)
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2025-01-01T{}Z", time).parse().unwrap()
+    }
+
+    fn rollup(start: &str, checks: u64, failures: u64, breaches: u64) -> Rollup {
+        Rollup {
+            url: "api".to_string(),
+            start: at(start),
+            seconds: BUCKET_SECONDS,
+            checks,
+            failures,
+            breaches,
+            latency_count: 0,
+            latency_sum_ms: 0,
+            latency_min_ms: None,
+            latency_max_ms: None,
+        }
+    }
+
+    #[test]
+    fn parse_window_accepts_minutes_to_weeks() {
+        assert_eq!(parse_window("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_window(" 24h ").unwrap(), Duration::hours(24));
+        assert_eq!(parse_window("7d").unwrap(), Duration::days(7));
+        assert_eq!(parse_window("2w").unwrap(), Duration::weeks(2));
+        for bad in ["", "h", "24", "0d", "5y", "-1h", "1.5h"] {
+            assert!(parse_window(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn window_start_rounds_up_to_the_next_bucket() {
+        assert_eq!(
+            window_start(at("12:00:30"), Duration::hours(1)),
+            at("11:01:00")
+        );
+        assert_eq!(
+            window_start(at("12:00:00"), Duration::hours(1)),
+            at("11:00:00")
+        );
+    }
+
+    #[test]
+    fn availability_skips_the_bucket_straddling_the_window_start() {
+        let store = Rollups(vec![
+            rollup("09:59:00", 6, 6, 0), // 一番長い期間より前
+            rollup("10:59:00", 6, 6, 0),
+            rollup("11:00:00", 6, 6, 0), // 1h の始まり（11:00:30）をまたぐ
+            rollup("11:30:00", 6, 1, 1),
+            rollup("12:00:00", 4, 0, 0),
+        ]);
+        let windows = ["1h".to_string(), "2h".to_string()];
+        let report = availability(&store, "api", 90.0, &windows, at("12:00:30")).unwrap();
+
+        let hour = &report[0];
+        assert_eq!((hour.from, hour.to), (at("11:01:00"), at("12:00:30")));
+        assert_eq!((hour.checks, hour.failures, hour.breaches), (10, 1, 1));
+        assert_eq!(hour.uptime_percent, Some(90.0));
+        assert_eq!(hour.sla_percent, Some(80.0));
+        // 10 回のうち 1 回まで許されていて、それを使い切った
+        assert_eq!(hour.error_budget_remaining_percent, Some(0.0));
+
+        let two = &report[1];
+        assert_eq!(two.from, at("10:01:00"));
+        assert_eq!((two.checks, two.failures), (22, 13));
+
+        let empty = availability(&Rollups(Vec::new()), "api", 99.9, &windows, at("12:00:30"));
+        assert_eq!(empty.unwrap()[0].uptime_percent, None);
+    }
+
+    #[test]
+    fn availability_keeps_compacted_rollups_straddling_the_window_start() {
+        let dir = std::env::temp_dir().join(format!("ruliadema-report-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut store = SqliteStore::open(dir.join("ruliadema.db")).unwrap();
+
+        for (at, status) in [
+            ("2025-01-08T11:30:00Z", CheckStatus::Up), // 2d より前の1時間
+            ("2025-01-08T12:10:00Z", CheckStatus::Down), // 2d の始まり（12:01）をまたぐ1時間
+            ("2025-01-08T12:50:00Z", CheckStatus::Up),
+            ("2025-01-10T11:00:00Z", CheckStatus::Up),
+        ] {
+            store
+                .append_result("api", "api", &result(at, status))
+                .unwrap();
+        }
+        let now: DateTime<Utc> = "2025-01-10T12:00:30Z".parse().unwrap();
+        let retention = Retention {
+            raw_days: 1,
+            rollup_seconds: 3600,
+            rollup_days: 0,
+            breach_days: 0,
+        };
+        assert_eq!(store.compact(&retention, now).unwrap().rolled_up, 3);
+
+        let report = availability(&store, "api", 99.0, &["2d".to_string()], now).unwrap();
+        let two = &report[0];
+        assert_eq!(
+            two.from,
+            "2025-01-08T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!((two.checks, two.failures), (3, 1));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
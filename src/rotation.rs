@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde_json::Value;
+
+use crate::config::RotationConfig;
+
+// 切り替えたファイルの名前に付ける日時（例: ruliadema.log.20250101T000000.000Z.gz）
+const STAMP: &str = "%Y%m%dT%H%M%S%.3fZ";
+const MB: u64 = 1024 * 1024;
+
+// 切り替え済みのファイル。rotated_at までに追記された記録を含む
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub path: PathBuf,
+    pub rotated_at: DateTime<Utc>,
+    pub compressed: bool,
+    pub bytes: u64,
+}
+
+impl Segment {
+    pub fn open(&self) -> io::Result<Box<dyn BufRead>> {
+        let file = File::open(&self.path)?;
+        Ok(if self.compressed {
+            Box::new(BufReader::new(GzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        })
+    }
+}
+
+// path の切り替え済みファイル（古い順）。圧縮途中の .tmp は含めず、圧縮前後の両方があれば圧縮前を使う
+pub fn segments(path: &Path) -> Vec<Segment> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let prefix = format!("{}.", name);
+    let mut segments: BTreeMap<DateTime<Utc>, Segment> = BTreeMap::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(rest) = file_name.to_str().and_then(|n| n.strip_prefix(&prefix)) else {
+            continue;
+        };
+        let (stamp, compressed) = match rest.strip_suffix(".gz") {
+            Some(stamp) => (stamp, true),
+            None => (rest, false),
+        };
+        let Ok(rotated_at) = NaiveDateTime::parse_from_str(stamp, STAMP) else {
+            continue;
+        };
+        let segment = Segment {
+            path: entry.path(),
+            rotated_at: rotated_at.and_utc(),
+            compressed,
+            bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+        };
+        match segments.entry(segment.rotated_at) {
+            Entry::Vacant(e) => {
+                e.insert(segment);
+            }
+            Entry::Occupied(mut e) if !compressed => {
+                e.insert(segment);
+            }
+            Entry::Occupied(_) => {}
+        }
+    }
+    segments.into_values().collect()
+}
+
+// 切り替え済みのファイルと今のファイルを古い順に開く。from より前に切り替えたファイルは読まない
+pub fn readers(path: &Path, from: Option<DateTime<Utc>>) -> Vec<Box<dyn BufRead>> {
+    let mut readers: Vec<Box<dyn BufRead>> = segments(path)
+        .iter()
+        .filter(|s| from.is_none_or(|from| s.rotated_at >= from))
+        .filter_map(|s| s.open().ok())
+        .collect();
+    if let Ok(file) = File::open(path) {
+        readers.push(Box::new(BufReader::new(file)));
+    }
+    readers
+}
+
+// JSON Lines の追記先。大きさか経過時間が上限を超えたら日時付きの名前に変え、新しいファイルに切り替える
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Option<RotationConfig>,
+    started: Option<DateTime<Utc>>, // 今のファイルの最初の記録の日時
+}
+
+impl RotatingFile {
+    pub fn new(path: PathBuf, rotation: Option<RotationConfig>) -> Self {
+        Self {
+            path,
+            rotation,
+            started: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&mut self, entry: &Value) -> Result<()> {
+        let now = Utc::now();
+        let line = format!("{}\n", serde_json::to_string(entry)?);
+        if self.due(line.len() as u64, now) {
+            self.rotate(now)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        self.started.get_or_insert(now);
+        Ok(())
+    }
+
+    // 今のファイルをディスクに同期する（まだなければ何もしない）
+    pub fn sync(&self) -> io::Result<()> {
+        match File::open(&self.path) {
+            Ok(file) => file.sync_all(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn due(&mut self, incoming: u64, now: DateTime<Utc>) -> bool {
+        let Some(rotation) = &self.rotation else {
+            return false;
+        };
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size == 0 {
+            return false;
+        }
+        // 再起動後は今のファイルの先頭の記録から経過時間を測る
+        let started = *self
+            .started
+            .get_or_insert_with(|| first_timestamp(&self.path).unwrap_or(now));
+        (rotation.max_size_mb > 0 && size + incoming > rotation.max_size_mb * MB)
+            || (rotation.max_age_hours > 0
+                && now - started >= Duration::hours(rotation.max_age_hours as i64))
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> Result<()> {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", now.format(STAMP)));
+        let segment = PathBuf::from(name);
+        fs::rename(&self.path, &segment)?;
+        self.started = None;
+
+        // 圧縮は追記を止めないよう別スレッドで行う
+        if self.rotation.as_ref().is_some_and(|r| r.compress) {
+            std::thread::spawn(move || {
+                if let Err(e) = compress(&segment) {
+                    eprintln!("[STORE] {} の圧縮に失敗しました: {}", segment.display(), e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    // 保存期間・合計の大きさを超えた切り替え済みのファイルを古い方から消し、消した数を返す
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<usize> {
+        let Some(rotation) = &self.rotation else {
+            return Ok(0);
+        };
+        let mut segments = segments(&self.path);
+        let mut removed = 0;
+
+        if rotation.retention_days > 0 {
+            let cutoff = now - Duration::days(rotation.retention_days as i64);
+            let expired = segments.partition_point(|s| s.rotated_at < cutoff);
+            for segment in segments.drain(..expired) {
+                fs::remove_file(&segment.path)?;
+                removed += 1;
+            }
+        }
+        if rotation.retention_mb > 0 {
+            let mut total: u64 = segments.iter().map(|s| s.bytes).sum();
+            for segment in &segments {
+                if total <= rotation.retention_mb * MB {
+                    break;
+                }
+                fs::remove_file(&segment.path)?;
+                total -= segment.bytes;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+// path を path.gz に圧縮して元のファイルを消す（書き終わるまでは .gz.tmp に書く）
+fn compress(path: &Path) -> io::Result<()> {
+    let mut gz = path.as_os_str().to_owned();
+    gz.push(".gz");
+    let mut tmp = gz.clone();
+    tmp.push(".tmp");
+
+    let mut encoder = GzEncoder::new(File::create(&tmp)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::rename(&tmp, &gz)?;
+    fs::remove_file(path)
+}
+
+// ファイルの最初の記録の result.timestamp
+fn first_timestamp(path: &Path) -> Option<DateTime<Utc>> {
+    let mut line = String::new();
+    BufReader::new(File::open(path).ok()?)
+        .read_line(&mut line)
+        .ok()?;
+    let entry: Value = serde_json::from_str(&line).ok()?;
+    entry["result"]["timestamp"].as_str()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // テストごとの空のディレクトリ
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ruliadema-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rotation(retention_days: u64, retention_mb: u64) -> RotationConfig {
+        RotationConfig {
+            max_size_mb: 0,
+            max_age_hours: 1,
+            compress: false,
+            retention_days,
+            retention_mb,
+        }
+    }
+
+    fn stamp(at: &str) -> String {
+        at.parse::<DateTime<Utc>>()
+            .unwrap()
+            .format(STAMP)
+            .to_string()
+    }
+
+    #[test]
+    fn segments_are_sorted_and_prefer_uncompressed_files() {
+        let dir = temp_dir("segments");
+        let log = dir.join("ruliadema.log");
+        let first = stamp("2025-01-01T00:00:00Z");
+        let second = stamp("2025-01-02T00:00:00Z");
+        for name in [
+            "ruliadema.log".to_string(),
+            format!("ruliadema.log.{}.gz", second),
+            format!("ruliadema.log.{}", first),
+            format!("ruliadema.log.{}.gz", first),
+            // 圧縮途中・日時でない・別のファイルは含めない
+            format!("ruliadema.log.{}.gz.tmp", stamp("2025-01-03T00:00:00Z")),
+            "ruliadema.log.old".to_string(),
+            format!("breaches.json.{}", first),
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let found = segments(&log);
+        let summary: Vec<(String, bool)> = found
+            .iter()
+            .map(|s| (s.rotated_at.to_rfc3339(), s.compressed))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("2025-01-01T00:00:00+00:00".to_string(), false),
+                ("2025-01-02T00:00:00+00:00".to_string(), true),
+            ]
+        );
+
+        // from より前に切り替えたファイルは読まない（今のファイルは常に読む）
+        assert_eq!(readers(&log, None).len(), 3);
+        assert_eq!(
+            readers(&log, Some("2025-01-01T12:00:00Z".parse().unwrap())).len(),
+            2
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn append_rotates_after_max_age() {
+        let dir = temp_dir("append");
+        let log = dir.join("ruliadema.log");
+        let old = Utc::now() - Duration::hours(2);
+        fs::write(
+            &log,
+            format!(
+                "{}\n",
+                serde_json::json!({ "result": { "timestamp": old } })
+            ),
+        )
+        .unwrap();
+
+        let mut file = RotatingFile::new(log.clone(), Some(rotation(0, 0)));
+        file.append(&serde_json::json!({ "n": 1 })).unwrap();
+        file.append(&serde_json::json!({ "n": 2 })).unwrap();
+
+        assert_eq!(segments(&log).len(), 1);
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_removes_expired_and_oversized_segments() {
+        let dir = temp_dir("prune");
+        let log = dir.join("ruliadema.log");
+        let now: DateTime<Utc> = "2025-01-10T00:00:00Z".parse().unwrap();
+        let chunk = vec![b'x'; (MB * 6 / 10) as usize];
+        for day in 1..=5 {
+            let at = format!("2025-01-0{}T00:00:00Z", day);
+            fs::write(dir.join(format!("ruliadema.log.{}", stamp(&at))), &chunk).unwrap();
+        }
+
+        // 7日より前（1日・2日）を消す
+        let file = RotatingFile::new(log.clone(), Some(rotation(7, 0)));
+        assert_eq!(file.prune(now).unwrap(), 2);
+        assert_eq!(segments(&log).len(), 3);
+
+        // 合計 1MB に収まるまで古い方から消す
+        let file = RotatingFile::new(log.clone(), Some(rotation(0, 1)));
+        assert_eq!(file.prune(now).unwrap(), 2);
+        let left = segments(&log);
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].rotated_at.to_rfc3339(), "2025-01-05T00:00:00+00:00");
+
+        // [storage.rotation] がなければ何も消さない
+        assert_eq!(RotatingFile::new(log, None).prune(now).unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::{Semaphore, mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::checker::Checkers;
+use crate::config::{Config, Target};
+use crate::model::CheckResult;
+
+// 監視タスクから集約側へ送る (ターゲットのキー, 結果)
+pub type Report = (String, CheckResult);
+
+// ターゲットごとの監視タスクを起動・停止する
+pub struct Scheduler {
+    checkers: Arc<Checkers>,
+    semaphore: Arc<Semaphore>,
+    default_interval: Duration,
+    tx: mpsc::Sender<Report>,
+    stopping: watch::Sender<bool>,
+    tasks: HashMap<String, (Target, JoinHandle<()>)>,
+}
+
+impl Scheduler {
+    pub fn new(config: &Config, tx: mpsc::Sender<Report>) -> anyhow::Result<Self> {
+        Ok(Self {
+            checkers: Arc::new(Checkers::new(
+                config.timeout_seconds,
+                config.retry_policy(),
+            )?),
+            semaphore: Arc::new(Semaphore::new(config.max_concurrency)),
+            default_interval: Duration::from_secs(config.interval_seconds),
+            tx,
+            stopping: watch::Sender::new(false),
+            tasks: HashMap::new(),
+        })
+    }
+
+    // 全体設定（既定の周期・タイムアウト・再試行・同時実行数）を差し替え、動いているタスクを作り直す
+    pub fn configure(&mut self, config: &Config) -> anyhow::Result<()> {
+        self.checkers = Arc::new(Checkers::new(
+            config.timeout_seconds,
+            config.retry_policy(),
+        )?);
+        self.semaphore = Arc::new(Semaphore::new(config.max_concurrency));
+        self.default_interval = Duration::from_secs(config.interval_seconds);
+
+        let targets: Vec<Target> = self.tasks.values().map(|(t, _)| t.clone()).collect();
+        for target in targets {
+            self.start(target);
+        }
+        Ok(())
+    }
+
+    // 同じキー（name / URL）のタスクが動いていれば止めてから起動し直す
+    pub fn start(&mut self, target: Target) {
+        self.stop(target.key());
+
+        let handle = tokio::spawn(run(
+            target.clone(),
+            target.interval(self.default_interval),
+            self.checkers.clone(),
+            self.semaphore.clone(),
+            self.tx.clone(),
+            self.stopping.subscribe(),
+        ));
+        self.tasks
+            .insert(target.key().to_string(), (target, handle));
+    }
+
+    // 次のチェックを始めないよう全タスクに伝え、実行中のチェックを待つためのハンドルを返す
+    // （タスクは実行中のチェックの結果を送ってから終わる。チャネルは Scheduler を捨てると閉じる）
+    pub fn shutdown(&mut self) -> Vec<JoinHandle<()>> {
+        self.stopping.send_replace(true);
+        self.tasks.drain().map(|(_, (_, handle))| handle).collect()
+    }
+
+    pub fn stop(&mut self, key: &str) -> bool {
+        match self.tasks.remove(key) {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// 1ターゲット分の監視ループ（前回のチェックが長引いた場合は次の周期を後ろにずらす）
+async fn run(
+    target: Target,
+    period: Duration,
+    checkers: Arc<Checkers>,
+    semaphore: Arc<Semaphore>,
+    tx: mpsc::Sender<Report>,
+    mut stopping: watch::Receiver<bool>,
+) {
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        // 停止の合図があれば、周期や空きを待っている間でもやめる（次の周期がもう来ていても停止を先に見る）
+        let permit = tokio::select! {
+            biased;
+            _ = stopping.wait_for(|stopping| *stopping) => return,
+            permit = async {
+                ticker.tick().await;
+                semaphore.clone().acquire_owned().await
+            } => permit,
+        };
+        let Ok(permit) = permit else {
+            return;
+        };
+        let result = checkers.check_once(&target).await;
+        drop(permit);
+
+        if tx.send((target.key().to_string(), result)).await.is_err() {
+            return;
+        }
+    }
+}
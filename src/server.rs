@@ -0,0 +1,544 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::sync::broadcast;
+
+use crate::config::{self, HttpConfig};
+use crate::incident::Incident;
+use crate::metrics::Metrics;
+use crate::model::CheckHistory;
+use crate::report::{self, TargetReport};
+use crate::store::{Store, TimeRange};
+
+// HTTP サーバから参照する daemon の状態（メインループがチェックのたびに更新する）
+#[derive(Debug)]
+pub struct Shared {
+    pub started_at: DateTime<Utc>,
+    pub config_path: String,
+    pub histories: HashMap<String, CheckHistory>,
+    pub incidents: Vec<Incident>,
+    pub metrics: Metrics,
+    pub events: broadcast::Sender<String>, // /api/events に流す SSE のフレーム
+}
+
+impl Shared {
+    // 接続中の /api/events に送る（誰も聞いていなければ捨てる）
+    pub fn publish(&self, event: &str, data: &impl Serialize) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+        if let Ok(data) = serde_json::to_string(data) {
+            let _ = self
+                .events
+                .send(format!("event: {}\ndata: {}\n\n", event, data));
+        }
+    }
+
+    // 同じ id の障害を差し替える（なければ末尾に足す）
+    pub fn upsert_incident(&mut self, incident: &Incident) {
+        match self
+            .incidents
+            .iter_mut()
+            .rev()
+            .find(|i| i.id == incident.id)
+        {
+            Some(existing) => *existing = incident.clone(),
+            None => self.incidents.push(incident.clone()),
+        }
+    }
+}
+
+pub type SharedState = Arc<RwLock<Shared>>;
+
+// API が読む保存先。起動時に1度だけ開いてリクエストの間で使い回す（SQLite は daemon の書き込みと並行して読める）
+type Reader = Arc<Mutex<Box<dyn Store>>>;
+
+// daemon に組み込む HTTP サーバ（/metrics と読み取り専用の /api/*）。store は API の読み取り用。待ち受けに失敗したらエラーを返す
+pub fn spawn(
+    http: &HttpConfig,
+    shared: SharedState,
+    store: Box<dyn Store>,
+) -> anyhow::Result<SocketAddr> {
+    let routes = Routes {
+        metrics: http.metrics,
+        api: http.api,
+    };
+    let reader: Reader = Arc::new(Mutex::new(store));
+    let make_service = make_service_fn(move |_| {
+        let shared = shared.clone();
+        let reader = reader.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let shared = shared.clone();
+                let reader = reader.clone();
+                async move { Ok::<_, Infallible>(handle(req, routes, shared, reader).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&http.listen.parse()?)?.serve(make_service);
+    let local_addr = server.local_addr();
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            eprintln!("[HTTP] サーバが停止しました: {}", e);
+        }
+    });
+    Ok(local_addr)
+}
+
+// [http] で有効にしたエンドポイント
+#[derive(Debug, Clone, Copy)]
+struct Routes {
+    metrics: bool,
+    api: bool,
+}
+
+async fn handle(
+    req: Request<Body>,
+    routes: Routes,
+    shared: SharedState,
+    reader: Reader,
+) -> Response<Body> {
+    if req.method() != Method::GET {
+        return error(StatusCode::METHOD_NOT_ALLOWED, "only GET is supported");
+    }
+    let query = Query::parse(req.uri().query());
+
+    let result = match req.uri().path() {
+        "/metrics" if routes.metrics => {
+            let body = shared
+                .read()
+                .map(|s| s.metrics.render())
+                .unwrap_or_default();
+            return Response::builder()
+                .header("Content-Type", "text/plain; version=0.0.4")
+                .body(Body::from(body))
+                .unwrap_or_default();
+        }
+        "/api/events" if routes.api => return events(&shared),
+        "/api/health" if routes.api => health(&shared),
+        "/api/targets" if routes.api => targets(&shared, query),
+        "/api/status" if routes.api => status(&shared, query),
+        "/api/history" if routes.api => history(&shared, &reader, query).await,
+        "/api/rollups" if routes.api => rollups(&shared, &reader, query).await,
+        "/api/breaches" if routes.api => breaches(&shared, &reader, query).await,
+        "/api/incidents" if routes.api => incidents(&shared, query),
+        "/api/report" if routes.api => report(&shared, &reader, query).await,
+        "/api/config" if routes.api => config(&shared).await,
+        _ => Err((StatusCode::NOT_FOUND, "not found".to_string())),
+    };
+
+    match result {
+        Ok(value) => Response::builder()
+            .header("Content-Type", "application/json")
+            .body(Body::from(value.to_string()))
+            .unwrap_or_default(),
+        Err((code, message)) => error(code, &message),
+    }
+}
+
+type ApiError = (StatusCode, String);
+type ApiResult = Result<Value, ApiError>;
+
+fn error(code: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .header("Content-Type", "application/json")
+        .body(Body::from(json!({ "error": message }).to_string()))
+        .unwrap_or_default()
+}
+
+fn internal(e: impl std::fmt::Display) -> ApiError {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+fn bad_request(message: String) -> ApiError {
+    (StatusCode::BAD_REQUEST, message)
+}
+
+// ?target=...&tag=...&from=...&to=...&limit=...&seconds=...&window=...&open=true（url= は target= と同じ）
+#[derive(Debug, Default)]
+struct Query {
+    target: Option<String>, // ターゲットのキー（name、なければ URL）
+    tag: Option<String>,
+    windows: Vec<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<String>,
+    seconds: Option<String>,
+    open: bool,
+}
+
+impl Query {
+    fn parse(query: Option<&str>) -> Self {
+        let mut parsed = Self::default();
+        for (key, value) in url::form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            let value = value.into_owned();
+            match key.as_ref() {
+                "target" | "url" => parsed.target = Some(value),
+                "tag" => parsed.tag = Some(value),
+                "window" => parsed.windows.push(value),
+                "from" => parsed.from = Some(value),
+                "to" => parsed.to = Some(value),
+                "limit" => parsed.limit = Some(value),
+                "seconds" => parsed.seconds = Some(value),
+                "open" => parsed.open = value == "true" || value == "1",
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    // 期間（RFC 3339）と件数の上限
+    fn range(&self) -> Result<TimeRange, ApiError> {
+        let time = |name: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| {
+                    DateTime::parse_from_rfc3339(v)
+                        .map(|t| t.with_timezone(&Utc))
+                        .map_err(|e| bad_request(format!("{} {:?}: {}", name, v, e)))
+                })
+                .transpose()
+        };
+        let limit = match &self.limit {
+            Some(v) => v
+                .parse()
+                .map_err(|_| bad_request(format!("limit {:?}: not a number", v)))?,
+            None => DEFAULT_LIMIT,
+        };
+        Ok(TimeRange {
+            from: time("from", &self.from)?,
+            to: time("to", &self.to)?,
+            limit: Some(limit),
+        })
+    }
+}
+
+const DEFAULT_LIMIT: usize = 1000;
+
+// Server-Sent Events。最初に status（全ターゲットのスナップショット）を送り、以降は変化を流す
+fn events(shared: &SharedState) -> Response<Body> {
+    let (mut rx, snapshot) = match shared.read() {
+        Ok(s) => (s.events.subscribe(), serde_json::to_string(&s.histories)),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+    let Ok(snapshot) = snapshot else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "state is unavailable");
+    };
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut frame = format!("event: status\ndata: {}\n\n", snapshot);
+        let mut keepalive = tokio::time::interval(KEEPALIVE);
+        keepalive.tick().await;
+        loop {
+            if sender.send_data(frame.into()).await.is_err() {
+                return; // クライアントが切断した
+            }
+            frame = tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(event) => event,
+                    // 取りこぼしたら切断し、クライアントにスナップショットから取り直させる
+                    Err(_) => return,
+                },
+                _ = keepalive.tick() => ": keepalive\n\n".to_string(),
+            };
+        }
+    });
+
+    Response::builder()
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(body)
+        .unwrap_or_default()
+}
+
+// 接続が生きていることをクライアントに知らせる間隔
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+fn health(shared: &SharedState) -> ApiResult {
+    let s = shared.read().map_err(internal)?;
+    let last_check = s
+        .histories
+        .values()
+        .filter_map(|h| h.results.back().map(|r| r.timestamp))
+        .max();
+
+    Ok(json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "started_at": s.started_at,
+        "uptime_seconds": (Utc::now() - s.started_at).num_seconds(),
+        "config": s.config_path,
+        "targets": s.histories.values().filter(|h| !h.is_retired()).count(),
+        "retired": s.histories.values().filter(|h| h.is_retired()).count(),
+        "down": s.histories.values().filter(|h| !h.is_retired() && h.state.down).count(),
+        "open_incidents": s.incidents.iter().filter(|i| i.is_open()).count(),
+        "last_check_at": last_check,
+    }))
+}
+
+// ターゲットごとの設定・状態・最新の結果（キー順）
+fn targets(shared: &SharedState, query: Query) -> ApiResult {
+    let s = shared.read().map_err(internal)?;
+    let mut histories: Vec<&CheckHistory> = s
+        .histories
+        .values()
+        .filter(|h| query.tag.as_ref().is_none_or(|tag| h.has_tag(tag)))
+        .collect();
+    histories.sort_by(|a, b| a.key().cmp(b.key()));
+
+    Ok(histories
+        .into_iter()
+        .map(|h| {
+            json!({
+                "target": h.key(),
+                "url": h.url,
+                "name": h.name,
+                "group": h.group,
+                "tags": h.tags,
+                "type": h.kind,
+                "acceptable_latency_ms": h.acceptable_latency_ms,
+                "latency_phase": h.latency_phase,
+                "state": h.state,
+                "retired_at": h.retired_at,
+                "cert_expires_at": h.results.back().and_then(|r| r.cert_expires_at),
+                "latest": h.results.back(),
+            })
+        })
+        .collect())
+}
+
+// status.json と同じ形のスナップショット
+fn status(shared: &SharedState, query: Query) -> ApiResult {
+    let s = shared.read().map_err(internal)?;
+    match &query.tag {
+        Some(tag) => serde_json::to_value(
+            s.histories
+                .iter()
+                .filter(|(_, h)| h.has_tag(tag))
+                .collect::<HashMap<_, _>>(),
+        ),
+        None => serde_json::to_value(&s.histories),
+    }
+    .map_err(internal)
+}
+
+// daemon が読んでいる設定ファイルの中身（view --remote の 'c' 用）。ヘッダの値や通知先の認証情報は伏せる
+async fn config(shared: &SharedState) -> ApiResult {
+    let path = shared.read().map_err(internal)?.config_path.clone();
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| internal(format!("{}: {}", path, e)))?;
+    let content =
+        config::redacted(&path, &content).map_err(|e| internal(format!("{}: {}", path, e)))?;
+    Ok(json!({ "path": path, "content": content }))
+}
+
+// 期間と重なる障害（古い順）
+fn incidents(shared: &SharedState, query: Query) -> ApiResult {
+    let range = query.range()?;
+    let s = shared.read().map_err(internal)?;
+    let matched: Vec<&Incident> = s
+        .incidents
+        .iter()
+        .filter(|i| query.target.as_ref().is_none_or(|key| i.key() == key))
+        .filter(|i| {
+            query
+                .tag
+                .as_ref()
+                .is_none_or(|tag| s.histories.get(i.key()).is_some_and(|h| h.has_tag(tag)))
+        })
+        .filter(|i| !query.open || i.is_open())
+        .filter(|i| range.to.is_none_or(|to| i.started_at <= to))
+        .filter(|i| {
+            range
+                .from
+                .is_none_or(|from| i.ended_at.is_none_or(|end| end >= from))
+        })
+        .collect();
+    let skip = matched
+        .len()
+        .saturating_sub(range.limit.unwrap_or(DEFAULT_LIMIT));
+    serde_json::to_value(&matched[skip..]).map_err(internal)
+}
+
+// 1ターゲット分の結果を期間で絞り込む
+async fn history(shared: &SharedState, reader: &Reader, query: Query) -> ApiResult {
+    let range = query.range()?;
+    let key = known_target(shared, query.target)?;
+    let results = with_store(reader, move |store| store.results(&key, &range)).await?;
+    serde_json::to_value(results).map_err(internal)
+}
+
+// seconds 秒（既定 3600）ごとにまとめた結果
+async fn rollups(shared: &SharedState, reader: &Reader, query: Query) -> ApiResult {
+    let range = query.range()?;
+    let seconds = match &query.seconds {
+        Some(v) => v
+            .parse()
+            .ok()
+            .filter(|&s: &u64| s > 0)
+            .ok_or_else(|| bad_request(format!("seconds {:?}: not a positive number", v)))?,
+        None => 3600,
+    };
+    let key = known_target(shared, query.target)?;
+    let rollups = with_store(reader, move |store| store.rollups(&key, &range, seconds)).await?;
+    serde_json::to_value(rollups).map_err(internal)
+}
+
+// 違反記録を期間で絞り込む（target・tag は省略可）
+async fn breaches(shared: &SharedState, reader: &Reader, query: Query) -> ApiResult {
+    let range = query.range()?;
+    let key = query.target;
+    let Some(tag) = query.tag else {
+        let breaches =
+            with_store(reader, move |store| store.breaches(key.as_deref(), &range)).await?;
+        return Ok(Value::Array(breaches));
+    };
+
+    // タグで絞ってから件数の上限をかける
+    let tagged: HashSet<String> = shared
+        .read()
+        .map_err(internal)?
+        .histories
+        .iter()
+        .filter(|(_, h)| h.has_tag(&tag))
+        .map(|(key, _)| key.clone())
+        .collect();
+    let all = TimeRange {
+        limit: None,
+        ..range
+    };
+    let mut breaches =
+        with_store(reader, move |store| store.breaches(key.as_deref(), &all)).await?;
+    breaches.retain(|b| {
+        b["name"]
+            .as_str()
+            .or(b["url"].as_str())
+            .is_some_and(|key| tagged.contains(key))
+    });
+    range.truncate(&mut breaches);
+    Ok(Value::Array(breaches))
+}
+
+// 期間ごとの稼働率・SLA 達成率・error budget の残り（target がなければ retired 以外の全ターゲット）
+async fn report(shared: &SharedState, reader: &Reader, query: Query) -> ApiResult {
+    let windows = if query.windows.is_empty() {
+        report::DEFAULT_WINDOWS.map(String::from).to_vec()
+    } else {
+        query.windows
+    };
+    for window in &windows {
+        report::parse_window(window).map_err(|e| bad_request(format!("{:#}", e)))?;
+    }
+
+    let mut histories: Vec<CheckHistory> = shared
+        .read()
+        .map_err(internal)?
+        .histories
+        .values()
+        .filter(|h| match &query.target {
+            Some(key) => h.key() == key,
+            None => !h.is_retired(),
+        })
+        .filter(|h| query.tag.as_ref().is_none_or(|tag| h.has_tag(tag)))
+        .cloned()
+        .collect();
+    if let Some(key) = &query.target
+        && histories.is_empty()
+    {
+        return Err((StatusCode::NOT_FOUND, format!("unknown target {}", key)));
+    }
+    histories.sort_by(|a, b| a.key().cmp(b.key()));
+
+    let reports = with_store(reader, move |store| {
+        histories
+            .iter()
+            .map(|h| TargetReport::new(store, h, &windows))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await?;
+    serde_json::to_value(reports).map_err(internal)
+}
+
+fn known_target(shared: &SharedState, key: Option<String>) -> Result<String, ApiError> {
+    let Some(key) = key else {
+        return Err(bad_request("target is required".to_string()));
+    };
+    if !shared
+        .read()
+        .map_err(internal)?
+        .histories
+        .contains_key(&key)
+    {
+        return Err((StatusCode::NOT_FOUND, format!("unknown target {}", key)));
+    }
+    Ok(key)
+}
+
+// 保存先を読む（ブロックするので別スレッドで）
+async fn with_store<T: Send + 'static>(
+    reader: &Reader,
+    read: impl FnOnce(&dyn Store) -> anyhow::Result<T> + Send + 'static,
+) -> Result<T, ApiError> {
+    let reader = reader.clone();
+    tokio::task::spawn_blocking(move || {
+        let store = reader.lock().map_err(|e| anyhow::anyhow!("{}", e))?;
+        read(store.as_ref())
+    })
+    .await
+    .map_err(internal)?
+    .map_err(|e| internal(format!("{:#}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parses_parameters() {
+        let query = Query::parse(Some(
+            "url=https%3A%2F%2Fexample.com%2F&tag=prod&window=24h&window=7d&open=1&limit=5",
+        ));
+        assert_eq!(query.target.as_deref(), Some("https://example.com/"));
+        assert_eq!(query.tag.as_deref(), Some("prod"));
+        assert_eq!(query.windows, ["24h", "7d"]);
+        assert!(query.open);
+        assert!(!Query::parse(Some("open=yes")).open);
+        assert!(Query::parse(None).target.is_none());
+    }
+
+    #[test]
+    fn range_parses_times_and_limit() {
+        let range = Query::parse(Some(
+            "from=2024-01-01T00:00:00Z&to=2024-01-02T09:00:00%2B09:00",
+        ))
+        .range()
+        .unwrap();
+        assert_eq!(
+            range.from.unwrap().to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert_eq!(range.to.unwrap().to_rfc3339(), "2024-01-02T00:00:00+00:00");
+        assert_eq!(range.limit, Some(DEFAULT_LIMIT));
+        assert_eq!(
+            Query::parse(Some("limit=10")).range().unwrap().limit,
+            Some(10)
+        );
+
+        for bad in ["from=yesterday", "to=2024-01-01", "limit=-1", "limit=ten"] {
+            let (code, _) = Query::parse(Some(bad)).range().unwrap_err();
+            assert_eq!(code, StatusCode::BAD_REQUEST, "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// status.json の形式。互換性のない変更をしたら上げる
+pub const SCHEMA_VERSION: u64 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot<T> {
+    version: u64,
+    saved_at: DateTime<Utc>,
+    targets: T,
+}
+
+// status.json を書き出す（一時ファイルに書いて fsync してから置き換えるので、読み手は途中の内容を見ない）
+pub fn save<T: Serialize>(path: &Path, targets: &T) -> Result<()> {
+    let snapshot = Snapshot {
+        version: SCHEMA_VERSION,
+        saved_at: Utc::now(),
+        targets,
+    };
+    write_atomic(path, &serde_json::to_vec_pretty(&snapshot)?)?;
+    Ok(())
+}
+
+// status.json を読む。ファイルがなければ空、壊れている・新しすぎる形式ならエラー
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e).with_context(|| format!("{} を開けません", path.display())),
+    };
+    let value: Value = serde_json::from_slice(&bytes)
+        .with_context(|| format!("{} が JSON として読めません", path.display()))?;
+
+    // version を持たないものは従来の形式（URL → 履歴 のマップ）
+    let Some(version) = value.get("version") else {
+        return serde_json::from_value(value)
+            .with_context(|| format!("{} の内容が読めません", path.display()));
+    };
+    match version.as_u64() {
+        Some(version) if version > SCHEMA_VERSION => bail!(
+            "{} は新しい形式（version {}）で書かれています。ruliadema を更新してください",
+            path.display(),
+            version
+        ),
+        Some(_) => {}
+        None => bail!("{} の version が数値ではありません", path.display()),
+    }
+    let snapshot: Snapshot<T> = serde_json::from_value(value)
+        .with_context(|| format!("{} の内容が読めません", path.display()))?;
+    Ok(snapshot.targets)
+}
+
+// 読めなかった status.json を上書きされないよう日時付きの名前に移し、移動先を返す
+pub fn quarantine(path: &Path) -> io::Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".corrupt-{}", Utc::now().format("%Y%m%dT%H%M%SZ")));
+    let moved = PathBuf::from(name);
+    fs::rename(path, &moved)?;
+    Ok(moved)
+}
+
+// 同じディレクトリの一時ファイルに書いてから rename で置き換える
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    // rename 自体を残すためにディレクトリも同期する
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
@@ -0,0 +1,509 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{RotationConfig, StorageBackend, StorageConfig};
+use crate::model::CheckResult;
+use crate::paths::DataDir;
+use crate::rotation::{self, RotatingFile};
+
+// 期間（両端を含む）と件数の上限。上限を超える場合は新しい方を残す
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl TimeRange {
+    // 直近 duration の間
+    pub fn last(duration: Duration) -> Self {
+        Self {
+            from: Some(Utc::now() - duration),
+            ..Self::default()
+        }
+    }
+
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at <= to)
+    }
+
+    pub fn truncate<T>(&self, items: &mut Vec<T>) {
+        if let Some(limit) = self.limit {
+            let skip = items.len().saturating_sub(limit);
+            items.drain(..skip);
+        }
+    }
+}
+
+// 一定時間ごとにまとめた結果（古いデータの間引き・長い期間のグラフ用）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rollup {
+    pub url: String, // ターゲットのキー（name、なければ URL）
+    pub start: DateTime<Utc>,
+    pub seconds: u64,
+    pub checks: u64,
+    pub failures: u64, // Down / Error / AssertionFailed
+    pub breaches: u64, // 成功したが許容時間を超えた
+    pub latency_count: u64,
+    pub latency_sum_ms: u64,
+    pub latency_min_ms: Option<u64>,
+    pub latency_max_ms: Option<u64>,
+}
+
+impl Rollup {
+    fn new(url: &str, start: DateTime<Utc>, seconds: u64) -> Self {
+        Self {
+            url: url.to_string(),
+            start,
+            seconds,
+            checks: 0,
+            failures: 0,
+            breaches: 0,
+            latency_count: 0,
+            latency_sum_ms: 0,
+            latency_min_ms: None,
+            latency_max_ms: None,
+        }
+    }
+
+    pub fn avg_latency_ms(&self) -> Option<f64> {
+        (self.latency_count > 0).then(|| self.latency_sum_ms as f64 / self.latency_count as f64)
+    }
+
+    fn add(&mut self, result: &CheckResult) {
+        self.checks += 1;
+        if result.is_failing() {
+            self.failures += 1;
+        }
+        if result.is_breached() {
+            self.breaches += 1;
+        }
+        if let Some(rt) = result.response_time {
+            let ms = rt.as_millis() as u64;
+            self.latency_count += 1;
+            self.latency_sum_ms += ms;
+            self.latency_min_ms = Some(self.latency_min_ms.map_or(ms, |m| m.min(ms)));
+            self.latency_max_ms = Some(self.latency_max_ms.map_or(ms, |m| m.max(ms)));
+        }
+    }
+
+    fn merge(&mut self, other: &Rollup) {
+        self.checks += other.checks;
+        self.failures += other.failures;
+        self.breaches += other.breaches;
+        self.latency_count += other.latency_count;
+        self.latency_sum_ms += other.latency_sum_ms;
+        self.latency_min_ms = min_opt(self.latency_min_ms, other.latency_min_ms);
+        self.latency_max_ms = self.latency_max_ms.max(other.latency_max_ms);
+    }
+}
+
+fn min_opt(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// at を含む seconds 秒区切りの区間の先頭
+fn bucket_start(at: DateTime<Utc>, seconds: u64) -> DateTime<Utc> {
+    let seconds = seconds.max(1) as i64;
+    let start = at.timestamp().div_euclid(seconds) * seconds;
+    Utc.timestamp_opt(start, 0).single().unwrap_or(at)
+}
+
+// 結果を seconds 秒ごとにまとめる（古い順）
+pub fn roll_up(url: &str, results: &[CheckResult], seconds: u64) -> Vec<Rollup> {
+    let mut buckets: BTreeMap<DateTime<Utc>, Rollup> = BTreeMap::new();
+    for result in results {
+        let start = bucket_start(result.timestamp, seconds);
+        buckets
+            .entry(start)
+            .or_insert_with(|| Rollup::new(url, start, seconds))
+            .add(result);
+    }
+    buckets.into_values().collect()
+}
+
+// 保存期間（日数、0 は無期限）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub raw_days: u64,
+    pub rollup_seconds: u64,
+    pub rollup_days: u64,
+    pub breach_days: u64,
+}
+
+// compact で間引き・削除した件数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compaction {
+    pub rolled_up: usize,
+    pub rollups_removed: usize,
+    pub breaches_removed: usize,
+    pub segments_removed: usize,
+}
+
+impl Compaction {
+    pub fn is_empty(&self) -> bool {
+        self.rolled_up == 0
+            && self.rollups_removed == 0
+            && self.breaches_removed == 0
+            && self.segments_removed == 0
+    }
+}
+
+// チェック結果と違反記録の保存先。key はターゲットのキー（name、なければ URL）
+pub trait Store: Send {
+    fn append_result(&mut self, key: &str, url: &str, result: &CheckResult) -> Result<()>;
+
+    // breaches.json と同じ形の記録（url と result.timestamp を含む）
+    fn append_breach(&mut self, key: &str, entry: &Value) -> Result<()>;
+
+    // 1ターゲットの結果（古い順）
+    fn results(&self, key: &str, range: &TimeRange) -> Result<Vec<CheckResult>>;
+
+    // 違反記録（古い順）。key が None なら全ターゲット
+    fn breaches(&self, key: Option<&str>, range: &TimeRange) -> Result<Vec<Value>>;
+
+    // seconds 秒ごとにまとめた結果（古い順）
+    fn rollups(&self, key: &str, range: &TimeRange, seconds: u64) -> Result<Vec<Rollup>> {
+        let results = self.results(
+            key,
+            &TimeRange {
+                limit: None,
+                ..*range
+            },
+        )?;
+        let mut rollups = roll_up(key, &results, seconds);
+        range.truncate(&mut rollups);
+        Ok(rollups)
+    }
+
+    // 書いた内容をディスクに落とす（終了前に呼ぶ）
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    // 保存期間を過ぎた結果をまとめ、まとめも違反記録も期限を過ぎたものを消す
+    fn compact(&mut self, _retention: &Retention, _now: DateTime<Utc>) -> Result<Compaction> {
+        Ok(Compaction::default())
+    }
+}
+
+// [storage] の backend に応じた保存先を開く
+pub fn open(config: &StorageConfig, data_dir: &DataDir) -> Result<Box<dyn Store>> {
+    Ok(match config.backend {
+        StorageBackend::Jsonl => Box::new(JsonlStore::new(data_dir, config.rotation.clone())),
+        StorageBackend::Sqlite => Box::new(SqliteStore::open(data_dir.database())?),
+    })
+}
+
+// ruliadema.log / breaches.json に JSON Lines で追記する（従来の形式。読むときは切り替え済みのファイルから順に走査する）
+pub struct JsonlStore {
+    log: RotatingFile,
+    breaches: RotatingFile,
+}
+
+impl JsonlStore {
+    pub fn new(data_dir: &DataDir, rotation: Option<RotationConfig>) -> Self {
+        Self {
+            log: RotatingFile::new(data_dir.log(), rotation.clone()),
+            breaches: RotatingFile::new(data_dir.breaches(), rotation),
+        }
+    }
+
+    // ターゲットのキー（name、なければ url）と result.timestamp で絞り込んだ行
+    fn scan(path: &Path, key: Option<&str>, range: &TimeRange) -> Vec<Value> {
+        rotation::readers(path, range.from)
+            .into_iter()
+            .flat_map(|reader| reader.lines().map_while(Result::ok))
+            .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+            .filter(|entry| {
+                let entry_key = entry["name"].as_str().or(entry["url"].as_str());
+                let same_target = key.is_none_or(|key| entry_key == Some(key));
+                let timestamp = entry["result"]["timestamp"]
+                    .as_str()
+                    .and_then(|t| t.parse::<DateTime<Utc>>().ok());
+                same_target && timestamp.is_some_and(|t| range.contains(t))
+            })
+            .collect()
+    }
+}
+
+impl Store for JsonlStore {
+    fn append_result(&mut self, key: &str, url: &str, result: &CheckResult) -> Result<()> {
+        let mut entry = serde_json::json!({
+            "url": url,
+            "response_time_ms": result.response_time.map(|d| d.as_millis() as u64).unwrap_or(0),
+            "result": result
+        });
+        // name のあるターゲットだけ付ける（従来の行と同じ形を保つ）
+        if key != url {
+            entry["name"] = Value::from(key);
+        }
+        self.log.append(&entry)
+    }
+
+    fn append_breach(&mut self, _key: &str, entry: &Value) -> Result<()> {
+        self.breaches.append(entry)
+    }
+
+    fn results(&self, key: &str, range: &TimeRange) -> Result<Vec<CheckResult>> {
+        let mut results: Vec<CheckResult> = Self::scan(self.log.path(), Some(key), range)
+            .into_iter()
+            .filter_map(|mut entry| serde_json::from_value(entry["result"].take()).ok())
+            .collect();
+        range.truncate(&mut results);
+        Ok(results)
+    }
+
+    fn breaches(&self, key: Option<&str>, range: &TimeRange) -> Result<Vec<Value>> {
+        let mut breaches = Self::scan(self.breaches.path(), key, range);
+        range.truncate(&mut breaches);
+        Ok(breaches)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.log.sync()?;
+        self.breaches.sync()?;
+        Ok(())
+    }
+
+    // 保存期間を過ぎた切り替え済みのファイルを消す（[storage.rotation]）
+    fn compact(&mut self, _retention: &Retention, now: DateTime<Utc>) -> Result<Compaction> {
+        Ok(Compaction {
+            segments_removed: self.log.prune(now)? + self.breaches.prune(now)?,
+            ..Compaction::default()
+        })
+    }
+}
+
+// ruliadema.db（SQLite）。ターゲットのキー（url 列）と日時の索引で期間を引き、古い結果はまとめて間引く
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let conn =
+            Connection::open(&path).with_context(|| format!("{} を開けません", path.display()))?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        // daemon の書き込み中も view / API から読めるようにする
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS results (
+                url TEXT NOT NULL,
+                ts INTEGER NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS results_url_ts ON results (url, ts);
+            CREATE INDEX IF NOT EXISTS results_ts ON results (ts);
+            CREATE TABLE IF NOT EXISTS breaches (
+                url TEXT NOT NULL,
+                ts INTEGER NOT NULL,
+                data TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS breaches_url_ts ON breaches (url, ts);
+            CREATE INDEX IF NOT EXISTS breaches_ts ON breaches (ts);
+            CREATE TABLE IF NOT EXISTS rollups (
+                url TEXT NOT NULL,
+                start INTEGER NOT NULL,
+                seconds INTEGER NOT NULL,
+                data TEXT NOT NULL,
+                PRIMARY KEY (url, start)
+            );",
+        )?;
+        Ok(Self { conn })
+    }
+
+    // ts の範囲（ミリ秒）
+    fn bounds(range: &TimeRange) -> (i64, i64) {
+        (
+            range.from.map_or(i64::MIN, |t| t.timestamp_millis()),
+            range.to.map_or(i64::MAX, |t| t.timestamp_millis()),
+        )
+    }
+
+    // 新しい方から limit 件を取り、古い順に並べ直す
+    fn select(&self, table: &str, url: Option<&str>, range: &TimeRange) -> Result<Vec<String>> {
+        let (from, to) = Self::bounds(range);
+        let limit = range.limit.map_or(-1, |l| l as i64);
+        let sql = format!(
+            "SELECT data FROM {} WHERE (?1 IS NULL OR url = ?1) AND ts BETWEEN ?2 AND ?3 ORDER BY ts DESC LIMIT ?4",
+            table
+        );
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let mut rows = stmt
+            .query_map(params![url, from, to, limit], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        rows.reverse();
+        Ok(rows)
+    }
+}
+
+impl Store for SqliteStore {
+    fn append_result(&mut self, key: &str, _url: &str, result: &CheckResult) -> Result<()> {
+        self.conn
+            .prepare_cached("INSERT INTO results (url, ts, data) VALUES (?1, ?2, ?3)")?
+            .execute(params![
+                key,
+                result.timestamp.timestamp_millis(),
+                serde_json::to_string(result)?
+            ])?;
+        Ok(())
+    }
+
+    fn append_breach(&mut self, key: &str, entry: &Value) -> Result<()> {
+        let ts = entry["result"]["timestamp"]
+            .as_str()
+            .and_then(|t| t.parse::<DateTime<Utc>>().ok())
+            .unwrap_or_else(Utc::now);
+        self.conn
+            .prepare_cached("INSERT INTO breaches (url, ts, data) VALUES (?1, ?2, ?3)")?
+            .execute(params![key, ts.timestamp_millis(), entry.to_string()])?;
+        Ok(())
+    }
+
+    fn results(&self, key: &str, range: &TimeRange) -> Result<Vec<CheckResult>> {
+        self.select("results", Some(key), range)?
+            .iter()
+            .map(|data| Ok(serde_json::from_str(data)?))
+            .collect()
+    }
+
+    fn breaches(&self, key: Option<&str>, range: &TimeRange) -> Result<Vec<Value>> {
+        self.select("breaches", key, range)?
+            .iter()
+            .map(|data| Ok(serde_json::from_str(data)?))
+            .collect()
+    }
+
+    // 間引き済みのまとめと、まだ残っている結果をまとめたものを seconds 秒ごとに合わせる
+    fn rollups(&self, key: &str, range: &TimeRange, seconds: u64) -> Result<Vec<Rollup>> {
+        let (from, to) = (
+            range.from.map_or(i64::MIN, |t| t.timestamp()),
+            range.to.map_or(i64::MAX, |t| t.timestamp()),
+        );
+        // 期間の始まりをまたぐまとめも含める（compact したまとめは rollup_minutes の長さがある）
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT data FROM rollups WHERE url = ?1 AND start + seconds > ?2 AND start <= ?3 ORDER BY start",
+        )?;
+        let stored = stmt
+            .query_map(params![key, from, to], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        let raw = self.results(
+            key,
+            &TimeRange {
+                limit: None,
+                ..*range
+            },
+        )?;
+
+        let mut buckets: BTreeMap<DateTime<Utc>, Rollup> = BTreeMap::new();
+        let stored = stored
+            .iter()
+            .filter_map(|data| serde_json::from_str::<Rollup>(data).ok());
+        for rollup in stored.chain(roll_up(key, &raw, seconds)) {
+            // seconds より長いまとめは分けられないので、その長さのまま1つの区間にする
+            let start = bucket_start(rollup.start, seconds);
+            let bucket = buckets
+                .entry(start)
+                .or_insert_with(|| Rollup::new(key, start, seconds));
+            bucket.seconds = bucket.seconds.max(rollup.seconds);
+            bucket.merge(&rollup);
+        }
+        let mut rollups: Vec<Rollup> = buckets.into_values().collect();
+        range.truncate(&mut rollups);
+        Ok(rollups)
+    }
+
+    // WAL の内容を本体に書き戻す
+    fn flush(&mut self) -> Result<()> {
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    fn compact(&mut self, retention: &Retention, now: DateTime<Utc>) -> Result<Compaction> {
+        let mut compaction = Compaction::default();
+        let tx = self.conn.transaction()?;
+
+        if retention.raw_days > 0 {
+            // まとめる区間の途中で切らないよう、区間の先頭に揃えた時刻より前を対象にする
+            let cutoff = bucket_start(
+                now - Duration::days(retention.raw_days as i64),
+                retention.rollup_seconds,
+            );
+            let old: Vec<(String, String)> = tx
+                .prepare("SELECT url, data FROM results WHERE ts < ?1 ORDER BY url, ts")?
+                .query_map(params![cutoff.timestamp_millis()], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .collect::<rusqlite::Result<_>>()?;
+
+            let mut by_url: BTreeMap<String, Vec<CheckResult>> = BTreeMap::new();
+            for (url, data) in &old {
+                if let Ok(result) = serde_json::from_str(data) {
+                    by_url.entry(url.clone()).or_default().push(result);
+                }
+            }
+            for (url, results) in &by_url {
+                for mut rollup in roll_up(url, results, retention.rollup_seconds) {
+                    // 以前の compact で同じ区間をまとめていれば足し合わせる
+                    let existing: Option<String> = tx
+                        .query_row(
+                            "SELECT data FROM rollups WHERE url = ?1 AND start = ?2",
+                            params![url, rollup.start.timestamp()],
+                            |row| row.get(0),
+                        )
+                        .optional()?;
+                    if let Some(existing) =
+                        existing.and_then(|d| serde_json::from_str::<Rollup>(&d).ok())
+                    {
+                        rollup.merge(&existing);
+                    }
+                    tx.execute(
+                        "INSERT OR REPLACE INTO rollups (url, start, seconds, data) VALUES (?1, ?2, ?3, ?4)",
+                        params![
+                            url,
+                            rollup.start.timestamp(),
+                            rollup.seconds as i64,
+                            serde_json::to_string(&rollup)?
+                        ],
+                    )?;
+                }
+            }
+            compaction.rolled_up = tx.execute(
+                "DELETE FROM results WHERE ts < ?1",
+                params![cutoff.timestamp_millis()],
+            )?;
+        }
+
+        if retention.rollup_days > 0 {
+            let cutoff = now - Duration::days(retention.rollup_days as i64);
+            compaction.rollups_removed = tx.execute(
+                "DELETE FROM rollups WHERE start < ?1",
+                params![cutoff.timestamp()],
+            )?;
+        }
+        if retention.breach_days > 0 {
+            let cutoff = now - Duration::days(retention.breach_days as i64);
+            compaction.breaches_removed = tx.execute(
+                "DELETE FROM breaches WHERE ts < ?1",
+                params![cutoff.timestamp_millis()],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(compaction)
+    }
+}